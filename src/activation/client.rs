@@ -0,0 +1,82 @@
+//! Client side of the activation protocol.
+
+use serde_json;
+use std::error;
+use std::fmt;
+use std::time::Duration;
+use ureq;
+use signed::VerifyingKey;
+use super::{ActivationRequest, ActivationResponse, ActivationToken, Rejection};
+use Key;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum ActivationError {
+    /// The server can't be reached or its answer can't be read.
+    Transport(String),
+    Rejected(Rejection),
+    /// The token isn't signed by the server or belongs to another key.
+    BadToken,
+}
+
+impl error::Error for ActivationError {}
+
+impl fmt::Display for ActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ActivationError::Transport(ref reason) => write!(f, "transport error: {}", reason),
+            ActivationError::Rejected(rejection) => write!(f, "rejected: {:?}", rejection),
+            ActivationError::BadToken => write!(f, "bad token"),
+        }
+    }
+}
+
+pub struct Client {
+    url: String,
+    public: VerifyingKey,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// `url` is the base address of the server, e.g. `https://example.com/license`.
+    pub fn new(url: &str, public: VerifyingKey) -> Self {
+        Client {
+            url: url.trim_end_matches('/').to_string(),
+            public,
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+        }
+    }
+
+    /// Activates the key on the machine and checks the returned token.
+    pub fn activate(&self, key: &Key, fingerprint: &str) -> Result<ActivationToken, ActivationError> {
+        let request = ActivationRequest {
+            key: key.clone(),
+            fingerprint: fingerprint.to_string(),
+        };
+        let body = serde_json::to_string(&request).expect("request is always serializable");
+        let url = format!("{}/activate", self.url);
+        let result = self.agent.post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&body);
+        let response = match result {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(ActivationError::Transport(e.to_string())),
+        };
+        let text = response.into_string()
+            .map_err(|e| ActivationError::Transport(e.to_string()))?;
+        let response = serde_json::from_str(&text)
+            .map_err(|e| ActivationError::Transport(e.to_string()))?;
+        match response {
+            ActivationResponse::Token(token) => {
+                if token.valid(&self.public, key, fingerprint) {
+                    Ok(token)
+                } else {
+                    Err(ActivationError::BadToken)
+                }
+            }
+            ActivationResponse::Error(rejection) => Err(ActivationError::Rejected(rejection)),
+        }
+    }
+}
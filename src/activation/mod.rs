@@ -0,0 +1,136 @@
+//! Online activation.
+//!
+//! The client posts an `ActivationRequest` with a key and a machine
+//! fingerprint to `/activate`. The server checks the key against the
+//! `Secret`, records the activation, enforces the activation limit and
+//! answers with an `ActivationToken` signed by its Ed25519 key:
+//!
+//! ```text
+//! POST /activate
+//! {"key": "007B-BFBF-3049-E324", "fingerprint": "..."}
+//!
+//! 200 OK
+//! {"token": {"seed": 123, "fingerprint": "...", "issued": 20743, "signature": "..."}}
+//!
+//! 403 Forbidden
+//! {"error": "limit-reached"}
+//! ```
+//!
+//! The client keeps the token and checks it offline with the public key.
+
+use ed25519_dalek::{Signature, Signer, Verifier as _};
+use serde::{Deserialize, Serialize};
+use hex;
+use signed::{SigningKey, VerifyingKey};
+use {Day, Key, KeyStatus, Seed};
+
+mod client;
+#[cfg(feature = "server")]
+pub mod server;
+
+pub use self::client::{ActivationError, Client};
+
+const CONTEXT: &[u8] = b"serial-number/activation";
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ActivationRequest {
+    pub key: Key,
+    pub fingerprint: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rejection {
+    /// The key is malformed or its checksum doesn't match.
+    InvalidKey,
+    Blacklisted,
+    /// The key wasn't produced by the secret.
+    Phony,
+    Expired,
+    /// The key is activated on too many machines.
+    LimitReached,
+    BadRequest,
+    /// The server failed to record the activation.
+    Unavailable,
+}
+
+impl Rejection {
+    /// Maps a failed key status, `None` for a valid key.
+    pub fn from_status(status: KeyStatus) -> Option<Self> {
+        match status {
+            KeyStatus::Valid => None,
+            KeyStatus::Invalid => Some(Rejection::InvalidKey),
+            KeyStatus::Blacklisted => Some(Rejection::Blacklisted),
+            KeyStatus::Phony(_) => Some(Rejection::Phony),
+            KeyStatus::Expired(_) => Some(Rejection::Expired),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivationResponse {
+    Token(ActivationToken),
+    Error(Rejection),
+}
+
+/// Proof that the key was activated on the machine.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct ActivationToken {
+    seed: Seed,
+    fingerprint: String,
+    issued: Day,
+    signature: String,
+}
+
+impl ActivationToken {
+    pub fn new(seed: Seed, fingerprint: &str, issued: Day, signing_key: &SigningKey) -> Self {
+        let signature = signing_key.sign(&message(seed, fingerprint, issued));
+        ActivationToken {
+            seed,
+            fingerprint: fingerprint.to_string(),
+            issued,
+            signature: hex::to_hex(&signature.to_bytes()),
+        }
+    }
+
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn issued(&self) -> Day {
+        self.issued
+    }
+
+    /// Checks the signature and that the token belongs to the key
+    /// and the machine.
+    pub fn valid(&self, public: &VerifyingKey, key: &Key, fingerprint: &str) -> bool {
+        if self.seed != key.seed() || self.fingerprint != fingerprint {
+            return false;
+        }
+        let signature = match hex::from_hex(&self.signature) {
+            Some(bytes) => Signature::from_slice(&bytes),
+            None => return false,
+        };
+        match signature {
+            Ok(signature) => {
+                let message = message(self.seed, &self.fingerprint, self.issued);
+                public.verify(&message, &signature).is_ok()
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+fn message(seed: Seed, fingerprint: &str, issued: Day) -> Vec<u8> {
+    let mut message = CONTEXT.to_vec();
+    message.extend_from_slice(&seed.to_be_bytes());
+    message.extend_from_slice(&(fingerprint.len() as u64).to_be_bytes());
+    message.extend_from_slice(fingerprint.as_bytes());
+    message.extend_from_slice(&issued.to_be_bytes());
+    message
+}
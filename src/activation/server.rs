@@ -0,0 +1,234 @@
+//! Reference implementation of the activation server.
+//!
+//! Activations are kept in a JSON lines file, one activation per line.
+//! A machine which activates the same key again gets a new token without
+//! using up the limit. The file is locked while an activation is checked
+//! and recorded, so several servers can share it.
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tiny_http::{Header, Method, Request, Response, Server};
+use journal;
+use signed::SigningKey;
+use super::{ActivationRequest, ActivationResponse, ActivationToken, Rejection};
+use {Clock, Day, Seed, SystemClock, Verifier};
+
+/// Requests with larger bodies are rejected.
+const MAX_BODY: u64 = 64 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    seed: Seed,
+    fingerprint: String,
+    activated: Day,
+}
+
+pub struct Store {
+    path: PathBuf,
+    activations: BTreeMap<Seed, BTreeSet<String>>,
+}
+
+impl Store {
+    /// Opens a store, a missing file is an empty store.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let activations = read_activations(&path)?;
+        Ok(Store { path, activations })
+    }
+
+    /// Number of machines the key with this seed is activated on.
+    pub fn activations(&self, seed: Seed) -> usize {
+        self.activations.get(&seed).map(BTreeSet::len).unwrap_or(0)
+    }
+
+    pub fn is_activated(&self, seed: Seed, fingerprint: &str) -> bool {
+        self.activations.get(&seed).is_some_and(|machines| machines.contains(fingerprint))
+    }
+
+    /// Records the activation unless the key is activated on `limit`
+    /// other machines, also by another server since the store was opened.
+    /// Returns `false` if the limit is reached.
+    fn record(&mut self, seed: Seed, fingerprint: &str, activated: Day, limit: usize) -> io::Result<bool> {
+        let mut file = journal::lock(&self.path)?;
+        self.activations = read_activations(&self.path)?;
+        if self.is_activated(seed, fingerprint) {
+            return Ok(true);
+        }
+        if self.activations(seed) >= limit {
+            return Ok(false);
+        }
+        let record = Record {
+            seed,
+            fingerprint: fingerprint.to_string(),
+            activated,
+        };
+        let line = serde_json::to_string(&record)?;
+        journal::append(&mut file, &[line])?;
+        self.activations.entry(seed).or_default().insert(record.fingerprint);
+        Ok(true)
+    }
+}
+
+fn read_activations(path: &Path) -> io::Result<BTreeMap<Seed, BTreeSet<String>>> {
+    let mut activations: BTreeMap<Seed, BTreeSet<String>> = BTreeMap::new();
+    for line in journal::read_lines(path)? {
+        let record: Record = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        activations.entry(record.seed).or_default().insert(record.fingerprint);
+    }
+    Ok(activations)
+}
+
+/// Checks keys and issues activation tokens.
+pub struct Authority {
+    verifier: Verifier,
+    signing_key: SigningKey,
+    store: Store,
+    limit: usize,
+    clock: Box<dyn Clock>,
+}
+
+impl Authority {
+    pub fn new(verifier: Verifier, signing_key: SigningKey, store: Store) -> Self {
+        Authority {
+            verifier,
+            signing_key,
+            store,
+            limit: 1,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Number of machines a key can be activated on, `1` by default.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets the clock for the issue dates of tokens.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn activate(&mut self, request: &ActivationRequest) -> Result<ActivationToken, Rejection> {
+        if let Some(rejection) = Rejection::from_status(self.verifier.verify(&request.key)) {
+            return Err(rejection);
+        }
+        let seed = request.key.seed();
+        let fingerprint = &request.fingerprint;
+        let today = self.clock.today();
+        let recorded = self.store.record(seed, fingerprint, today, self.limit)
+            .map_err(|_| Rejection::Unavailable)?;
+        if !recorded {
+            return Err(Rejection::LimitReached);
+        }
+        Ok(ActivationToken::new(seed, fingerprint, today, &self.signing_key))
+    }
+}
+
+/// Answers a single HTTP request.
+pub fn handle(authority: &mut Authority, mut request: Request) -> io::Result<()> {
+    let (code, response) = if *request.method() != Method::Post || request.url() != "/activate" {
+        (404, ActivationResponse::Error(Rejection::BadRequest))
+    } else {
+        let mut body = String::new();
+        let parsed = request.as_reader()
+            .take(MAX_BODY)
+            .read_to_string(&mut body)
+            .ok()
+            .and_then(|_| serde_json::from_str::<ActivationRequest>(&body).ok());
+        match parsed {
+            Some(parsed) => {
+                match authority.activate(&parsed) {
+                    Ok(token) => (200, ActivationResponse::Token(token)),
+                    Err(Rejection::Unavailable) => (503, ActivationResponse::Error(Rejection::Unavailable)),
+                    Err(rejection) => (403, ActivationResponse::Error(rejection)),
+                }
+            }
+            None => (400, ActivationResponse::Error(Rejection::BadRequest)),
+        }
+    };
+    let json = serde_json::to_string(&response)?;
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("header is valid");
+    let response = Response::from_string(json).with_status_code(code).with_header(header);
+    request.respond(response)
+}
+
+/// Answers requests until the server is closed. Errors of single
+/// requests, e.g. clients which went away, are logged and skipped.
+pub fn serve(server: &Server, authority: &mut Authority) {
+    for request in server.incoming_requests() {
+        if let Err(e) = handle(authority, request) {
+            eprintln!("request failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use activation::{ActivationError, Client};
+    use std::str::FromStr;
+    use std::thread;
+    use {Key, Secret};
+    use testing::TempPath;
+
+    #[test]
+    fn test_activation() {
+        let path = TempPath::new("activations");
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let signing_key = SigningKey::from_bytes(&[5; 32]);
+        let public = signing_key.verifying_key();
+        let store = Store::open(&path).unwrap();
+        let mut authority = Authority::new(Verifier::new(secret.clone()), signing_key, store)
+            .with_limit(1)
+            .with_clock(20_000);
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let client = Client::new(&format!("http://{}", server.server_addr()), public);
+        let key = Key::new(123, &secret);
+        let results = thread::spawn(move || {
+            let forged = Key::from_str("007B-BFBF-3049-E325").unwrap();
+            vec![
+                client.activate(&key, "machine-a"),
+                // The same machine again doesn't use up the limit
+                client.activate(&key, "machine-a"),
+                client.activate(&key, "machine-b"),
+                client.activate(&forged, "machine-b"),
+            ]
+        });
+        for _ in 0..4 {
+            handle(&mut authority, server.recv().unwrap()).unwrap();
+        }
+        let results = results.join().unwrap();
+
+        let key = Key::new(123, &secret);
+        let token = results[0].as_ref().unwrap();
+        assert_eq!(token.issued(), 20_000);
+        assert!(token.valid(&public, &key, "machine-a"));
+        assert!(!token.valid(&public, &key, "machine-b"));
+        assert!(results[1].is_ok());
+        match results[2] {
+            Err(ActivationError::Rejected(Rejection::LimitReached)) => {}
+            ref other => panic!("unexpected result: {:?}", other),
+        }
+        match results[3] {
+            Err(ActivationError::Rejected(Rejection::InvalidKey)) => {}
+            ref other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.activations(123), 1);
+        // Another server which opened the store earlier sees the activation
+        let mut other = Store::open(&path).unwrap();
+        assert!(store.record(124, "machine-a", 20_000, 1).unwrap());
+        assert!(!other.record(124, "machine-b", 20_000, 1).unwrap());
+        assert!(other.record(124, "machine-a", 20_000, 1).unwrap());
+        assert_eq!(Store::open(&path).unwrap().activations(124), 1);
+    }
+}
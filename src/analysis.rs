@@ -0,0 +1,199 @@
+//! Estimates how well a secret resists recovery from leaked keys.
+//!
+//! An attacker who has valid keys can search the whole parameter space of
+//! `Block::produce` and keep only the parameters which match every leaked
+//! key. Once all remaining candidates behave the same, the block is
+//! reconstructed and the attacker can produce that group for any seed.
+//! Groups which are reconstructed with few keys must be held back from
+//! releases, see `Secret::partial`.
+//!
+//! The derivation of payload seeds is public, only the mask of their
+//! groups is secret. It's a single byte per block, so payload keys are
+//! searched with the mask as an unknown parameter. Keys bound to an
+//! identity can't be derived without the identity and must not be passed.
+
+use alloc::vec::Vec;
+use audit::sample_seeds;
+use {derive, Block, Byte, Derived, Domain, Key, Seed};
+
+/// Candidates are checked for equivalence only below this count.
+const MAX_EQUIVALENCE_CHECK: usize = 1024;
+
+/// Recovery estimate of a single group.
+#[derive(Debug, Clone)]
+pub struct GroupRecovery {
+    pub group: usize,
+    /// Number of candidate parameters left for the left and right blocks
+    /// after all keys.
+    pub candidates: (usize, usize),
+    /// Number of keys after which the group is reconstructed,
+    /// `None` if the keys aren't enough.
+    pub keys_needed: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub keys: usize,
+    pub groups: Vec<GroupRecovery>,
+}
+
+impl Analysis {
+    /// Indices of the groups which can be reconstructed from the keys.
+    pub fn exposed(&self) -> Vec<usize> {
+        self.groups.iter()
+            .filter(|recovery| recovery.keys_needed.is_some())
+            .map(|recovery| recovery.group)
+            .collect()
+    }
+}
+
+/// All blocks which behave differently in `Block::produce`.
+fn candidates() -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for shift in 0..25 {
+        for &even in &[true, false] {
+            let a = if (shift % 2 == 0) == even { shift } else { shift + 25 };
+            for b in 0..3 {
+                for c in 0..=255 {
+                    blocks.push(Block::new(a, b, c));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Parameters of a block with the mask of its payload groups.
+struct Candidate {
+    block: Block,
+    mask: Byte,
+}
+
+struct Search {
+    candidates: Vec<Candidate>,
+    /// A plain key was seen.
+    plain: bool,
+    /// A payload key was seen and the masks are set.
+    masked: bool,
+    reconstructed: Option<usize>,
+}
+
+impl Search {
+    fn new() -> Self {
+        Search {
+            candidates: candidates().into_iter().map(|block| Candidate { block, mask: 0 }).collect(),
+            plain: false,
+            masked: false,
+            reconstructed: None,
+        }
+    }
+
+    fn narrow(&mut self, derived: Derived, byte: Byte, keys: usize, samples: &[Seed]) {
+        let seed = derived.value;
+        if derived.domain == Domain::Plain {
+            self.plain = true;
+            self.candidates.retain(|candidate| candidate.block.produce(seed) == byte);
+        } else if self.masked {
+            self.candidates.retain(|candidate| candidate.block.produce(seed) ^ candidate.mask == byte);
+        } else {
+            // The first payload key gives the mask of every candidate
+            self.masked = true;
+            for candidate in &mut self.candidates {
+                candidate.mask = candidate.block.produce(seed) ^ byte;
+            }
+        }
+        if self.reconstructed.is_none() && self.is_equivalent(samples) {
+            self.reconstructed = Some(keys);
+        }
+    }
+
+    /// Checks that all candidates produce the same groups in a domain
+    /// of the seen keys.
+    fn is_equivalent(&self, samples: &[Seed]) -> bool {
+        let candidates = &self.candidates;
+        if candidates.is_empty() || candidates.len() > MAX_EQUIVALENCE_CHECK {
+            return false;
+        }
+        let first = &candidates[0];
+        let same = |mask: fn(&Candidate) -> Byte| {
+            candidates[1..].iter().all(|candidate| {
+                samples.iter().all(|&seed| {
+                    candidate.block.produce(seed) ^ mask(candidate) == first.block.produce(seed) ^ mask(first)
+                })
+            })
+        };
+        (self.plain && same(|_| 0)) || (self.masked && same(|candidate| candidate.mask))
+    }
+}
+
+/// Analyzes the known valid keys. All keys must belong to the same secret,
+/// keys with a different number of groups than the first one are skipped.
+/// Plain and payload keys can be mixed.
+pub fn analyze(keys: &[Key]) -> Analysis {
+    let samples = sample_seeds();
+    let count = keys.first().map(|key| key.groups.len()).unwrap_or(0);
+    let mut searches: Vec<(Search, Search)> = (0..count)
+        .map(|_| (Search::new(), Search::new()))
+        .collect();
+    let mut used = 0;
+    for key in keys.iter().filter(|key| key.groups.len() == count) {
+        used += 1;
+        let derived = derive(key.seed, None);
+        for ((left, right), group) in searches.iter_mut().zip(&key.groups) {
+            left.narrow(derived, group.left, used, &samples);
+            right.narrow(derived, group.right, used, &samples);
+        }
+    }
+    let groups = searches.into_iter().enumerate().map(|(idx, (left, right))| {
+        let keys_needed = match (left.reconstructed, right.reconstructed) {
+            (Some(left), Some(right)) => Some(left.max(right)),
+            _ => None,
+        };
+        GroupRecovery {
+            group: idx,
+            candidates: (left.candidates.len(), right.candidates.len()),
+            keys_needed,
+        }
+    }).collect();
+    Analysis {
+        keys: used,
+        groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use {Day, Payload, Secret};
+
+    #[test]
+    fn test_analyze() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let keys: Vec<Key> = (0..24)
+            .map(|i: Seed| Key::new(i.wrapping_mul(0x2545_F491), &secret))
+            .collect();
+        let analysis = analyze(&keys[..1]);
+        assert!(analysis.exposed().is_empty());
+        let analysis = analyze(&keys);
+        assert_eq!(analysis.keys, 24);
+        assert_eq!(analysis.exposed(), vec![0, 1]);
+        for recovery in &analysis.groups {
+            assert!(recovery.candidates.0 >= 1 && recovery.candidates.1 >= 1);
+        }
+    }
+
+    #[test]
+    fn test_analyze_payload() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let keys: Vec<Key> = (0..40)
+            .map(|i: u32| {
+                let payload = Payload::new(i.wrapping_mul(0x2545_F491)).expires(20_000 + i as Day);
+                Key::new(payload.seed(), &secret)
+            })
+            .collect();
+        let analysis = analyze(&keys);
+        assert_eq!(analysis.keys, 40);
+        assert_eq!(analysis.exposed(), vec![0, 1]);
+    }
+}
@@ -0,0 +1,281 @@
+//! Detection of clocks moved back.
+//!
+//! An `AnchorStore` keeps the latest trusted time seen, in seconds since
+//! the epoch. It is raised by the store's own records, by modification
+//! times of files and by timestamps signed by the vendor. A clock far
+//! behind the anchor was moved back, and expiry checks against it
+//! shouldn't be trusted:
+//!
+//! ```no_run
+//! use serial_number::anchor::AnchorStore;
+//! use serial_number::payload::now;
+//! use serial_number::{PartialSecret, Verifier};
+//!
+//! let secret: PartialSecret = "?-ABB734930FCD".parse().unwrap();
+//! let mut anchors = AnchorStore::open("anchor.txt", &secret).unwrap();
+//! anchors.observe_mtimes(&["/var/log/wtmp"]).unwrap();
+//! if anchors.is_plausible(now()) {
+//!     anchors.record(now()).unwrap();
+//! }
+//! let verifier = Verifier::new(secret).with_clock(anchors.clock());
+//! ```
+//!
+//! The store file has a MAC keyed with material derived from the known
+//! groups of the secret:
+//!
+//! ```text
+//! serial-number anchor
+//! latest: 1792224000
+//! mac: 5B1E...C0
+//! ```
+//!
+//! Removing the file resets the store, so combine it with other anchors.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use document;
+use hex;
+use mac;
+use payload::{now, seconds};
+use {Clock, Day, PartialSecret};
+#[cfg(feature = "signed")]
+pub use self::signed::SignedTimestamp;
+
+const HEADER: &str = "serial-number anchor";
+
+const PURPOSE: &str = "serial-number/anchor";
+
+/// Clock corrections, e.g. by NTP, up to this many seconds back are
+/// plausible.
+const TOLERANCE: u64 = 60 * 60;
+
+/// Persisted latest trusted time.
+#[derive(Debug)]
+pub struct AnchorStore {
+    path: PathBuf,
+    secret: PartialSecret,
+    latest: u64,
+}
+
+impl AnchorStore {
+    /// Opens a store, a missing file is an empty store. Fails with
+    /// `InvalidData` if the file was changed. The modification time
+    /// of the file is an anchor too. The secret of a release is enough.
+    pub fn open<P: AsRef<Path>>(path: P, secret: &PartialSecret) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let latest = match fs::read_to_string(&path) {
+            Ok(text) => unseal(&text, secret)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let mut store = AnchorStore {
+            path,
+            secret: secret.clone(),
+            latest,
+        };
+        let own = store.path.clone();
+        store.observe_mtimes(&[own])?;
+        Ok(store)
+    }
+
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+
+    /// Raises the anchor to a trusted time, returns `true` if it was
+    /// later than the anchor.
+    pub fn observe(&mut self, time: u64) -> io::Result<bool> {
+        if time <= self.latest {
+            return Ok(false);
+        }
+        self.latest = time;
+        let tag = mac::compute(&self.secret, PURPOSE, &[time.to_string()]);
+        let text = format!("{}\nlatest: {}\nmac: {}\n", HEADER, time, hex::to_hex(&tag));
+        fs::write(&self.path, text)?;
+        Ok(true)
+    }
+
+    /// Raises the anchor to the modification times of the files,
+    /// missing files are skipped.
+    pub fn observe_mtimes<P: AsRef<Path>>(&mut self, paths: &[P]) -> io::Result<()> {
+        for path in paths {
+            let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            self.observe(seconds(modified))?;
+        }
+        Ok(())
+    }
+
+    /// Records the current time, call it after `is_plausible`.
+    pub fn record(&mut self, now: u64) -> io::Result<()> {
+        self.observe(now).map(|_| ())
+    }
+
+    /// Checks that `now` isn't behind the anchor.
+    pub fn is_plausible(&self, now: u64) -> bool {
+        now + TOLERANCE >= self.latest
+    }
+
+    /// Clock for verifiers which never goes behind the anchor.
+    pub fn clock(&self) -> AnchoredClock {
+        AnchoredClock {
+            latest: self.latest,
+        }
+    }
+}
+
+fn unseal(text: &str, secret: &PartialSecret) -> io::Result<u64> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "anchor is tampered");
+    let (fields, tag) = document::parse(text, HEADER, "mac").map_err(|_| invalid())?;
+    let latest = match fields[..] {
+        [("latest", latest)] => latest,
+        _ => return Err(invalid()),
+    };
+    let tag = tag.and_then(hex::from_hex).ok_or_else(invalid)?;
+    if !mac::verify(secret, PURPOSE, &[latest], &tag) {
+        return Err(invalid());
+    }
+    latest.parse().map_err(|_| invalid())
+}
+
+/// System clock which reads a time behind the anchor as the anchor,
+/// so moving the clock back doesn't revive expired keys.
+#[derive(Debug, Clone, Copy)]
+pub struct AnchoredClock {
+    latest: u64,
+}
+
+impl Clock for AnchoredClock {
+    fn today(&self) -> Day {
+        (now().max(self.latest) / 86_400) as Day
+    }
+}
+
+#[cfg(feature = "signed")]
+mod signed {
+    use ed25519_dalek::{Signature, Signer, Verifier as _};
+    use std::fmt;
+    use std::io;
+    use std::str;
+    use hex;
+    use signed::{SigningKey, VerifyingKey};
+    use super::AnchorStore;
+    use Error;
+
+    const CONTEXT: &[u8] = b"serial-number/timestamp";
+
+    /// Time signed by the vendor, e.g. sent with update checks.
+    /// Written as the seconds and the signature: `1792224000:9C1F...0B`.
+    #[derive(PartialEq, Eq, Debug, Clone)]
+    pub struct SignedTimestamp {
+        time: u64,
+        signature: Signature,
+    }
+
+    impl SignedTimestamp {
+        pub fn new(time: u64, signing_key: &SigningKey) -> Self {
+            SignedTimestamp {
+                time,
+                signature: signing_key.sign(&message(time)),
+            }
+        }
+
+        pub fn time(&self) -> u64 {
+            self.time
+        }
+
+        pub fn valid(&self, public: &VerifyingKey) -> bool {
+            public.verify(&message(self.time), &self.signature).is_ok()
+        }
+    }
+
+    fn message(time: u64) -> Vec<u8> {
+        let mut message = CONTEXT.to_vec();
+        message.extend_from_slice(&time.to_be_bytes());
+        message
+    }
+
+    impl fmt::Display for SignedTimestamp {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}:{}", self.time, hex::to_hex(&self.signature.to_bytes()))
+        }
+    }
+
+    impl str::FromStr for SignedTimestamp {
+        type Err = Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut parts = s.splitn(2, ':');
+            let time = parts.next().unwrap_or("").parse()?;
+            let bytes = parts.next()
+                .and_then(hex::from_hex)
+                .ok_or(Error::InvalidFragment)?;
+            let signature = Signature::from_slice(&bytes).map_err(|_| Error::InvalidFragment)?;
+            Ok(SignedTimestamp { time, signature })
+        }
+    }
+
+    impl AnchorStore {
+        /// Raises the anchor to a timestamp signed by the vendor.
+        /// Fails with `InvalidData` if the signature doesn't match.
+        pub fn observe_signed(&mut self, stamp: &SignedTimestamp, public: &VerifyingKey) -> io::Result<bool> {
+            if !stamp.valid(public) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "bad timestamp signature"));
+            }
+            self.observe(stamp.time)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use testing::TempPath;
+
+    #[test]
+    fn test_anchor() {
+        let path = TempPath::new("anchor");
+        let secret = PartialSecret::from_str("?-ABB734930FCD").unwrap();
+        let mut anchors = AnchorStore::open(&path, &secret).unwrap();
+        assert_eq!(anchors.latest(), 0);
+        assert!(anchors.is_plausible(0));
+
+        anchors.record(now()).unwrap();
+        assert!(anchors.is_plausible(now()));
+        assert!(!anchors.is_plausible(now() - 2 * 86_400));
+        let restored = AnchorStore::open(&path, &secret).unwrap();
+        assert!(restored.latest() >= anchors.latest());
+
+        let future = now() + 86_400;
+        assert!(anchors.observe(future).unwrap());
+        assert!(!anchors.observe(future - 1).unwrap());
+        assert!(!anchors.is_plausible(now()));
+        assert_eq!(anchors.clock().today(), (future / 86_400) as Day);
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::write(&path, text.replace(&future.to_string(), "0")).unwrap();
+        let err = AnchorStore::open(&path, &secret).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::write(&path, "0\n").unwrap();
+        assert!(AnchorStore::open(&path, &secret).is_err());
+        fs::write(&path, text).unwrap();
+        assert!(AnchorStore::open(&path, &secret).unwrap().latest() >= future);
+
+        #[cfg(feature = "signed")]
+        {
+            use signed::SigningKey;
+
+            let signing_key = SigningKey::from_bytes(&[3; 32]);
+            let stamp = SignedTimestamp::new(future + 60, &signing_key);
+            let stamp = SignedTimestamp::from_str(&stamp.to_string()).unwrap();
+            assert!(anchors.observe_signed(&stamp, &signing_key.verifying_key()).unwrap());
+            let other = SigningKey::from_bytes(&[4; 32]).verifying_key();
+            assert!(anchors.observe_signed(&stamp, &other).is_err());
+        }
+    }
+}
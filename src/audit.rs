@@ -0,0 +1,132 @@
+//! Audit of secrets before a release.
+//!
+//! Every block is run over a sample of the seed space and gets a score:
+//! the number of distinct bytes it produces. Blocks with degenerate
+//! parameters are flagged.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use {Block, Group, Secret, Seed};
+
+/// Number of seeds sampled for every block.
+const SAMPLES: u32 = 4096;
+
+/// Blocks which produce fewer distinct bytes are flagged.
+const MIN_DISTINCT: usize = 64;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Position {
+    pub group: usize,
+    pub side: Side,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Weakness {
+    /// The block produces the same byte for every seed.
+    Constant,
+    /// The block produces only this number of distinct bytes.
+    LowVariety(usize),
+    /// The block produces the same bytes as the block at this position.
+    Duplicate(Position),
+    /// The parameters of the block are weak, see `Block::is_weak`.
+    WeakParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockReport {
+    pub position: Position,
+    /// Number of distinct bytes produced over the sampled seeds.
+    pub distinct: usize,
+    pub weaknesses: Vec<Weakness>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub blocks: Vec<BlockReport>,
+}
+
+impl Report {
+    /// `true` if no block was flagged.
+    pub fn is_clean(&self) -> bool {
+        self.blocks.iter().all(|block| block.weaknesses.is_empty())
+    }
+
+    pub fn flagged(&self) -> Vec<&BlockReport> {
+        self.blocks.iter().filter(|block| !block.weaknesses.is_empty()).collect()
+    }
+}
+
+/// Seeds spread evenly over the 32 bits which `Block::produce` reads.
+pub(crate) fn sample_seeds() -> Vec<Seed> {
+    (0..SAMPLES).map(|i| i.wrapping_mul(0x9E37_79B9) as Seed).collect()
+}
+
+fn blocks(secret: &Secret) -> Vec<(Position, &Block)> {
+    let mut blocks = Vec::new();
+    for (idx, Group { left, right }) in secret.0.iter().enumerate() {
+        blocks.push((Position { group: idx, side: Side::Left }, left));
+        blocks.push((Position { group: idx, side: Side::Right }, right));
+    }
+    blocks
+}
+
+pub fn audit(secret: &Secret) -> Report {
+    let seeds = sample_seeds();
+    let mut outputs: Vec<(Position, Vec<u8>)> = Vec::new();
+    let mut reports = Vec::new();
+    for (position, block) in blocks(secret) {
+        let output: Vec<u8> = seeds.iter().map(|&seed| block.produce(seed)).collect();
+        let distinct = output.iter().collect::<BTreeSet<_>>().len();
+        let mut weaknesses = Vec::new();
+        if distinct == 1 {
+            weaknesses.push(Weakness::Constant);
+        } else if distinct < MIN_DISTINCT {
+            weaknesses.push(Weakness::LowVariety(distinct));
+        }
+        if let Some(&(other, _)) = outputs.iter().find(|&(_, prev)| prev == &output) {
+            weaknesses.push(Weakness::Duplicate(other));
+        }
+        if block.is_weak() {
+            weaknesses.push(Weakness::WeakParams);
+        }
+        outputs.push((position, output));
+        reports.push(BlockReport {
+            position,
+            distinct,
+            weaknesses,
+        });
+    }
+    Report { blocks: reports }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_audit() {
+        // The left block of the first group uses the same shift twice
+        // and an empty OR mask, so it always produces 0.
+        // The second group repeats the first one.
+        let secret = Secret::from_str("00000000F00F-00000000F00F").unwrap();
+        let report = audit(&secret);
+        assert!(!report.is_clean());
+        let first = &report.blocks[0];
+        assert_eq!(first.distinct, 1);
+        assert!(first.weaknesses.contains(&Weakness::Constant));
+        let third = &report.blocks[2];
+        let position = Position { group: 0, side: Side::Left };
+        assert!(third.weaknesses.contains(&Weakness::Duplicate(position)));
+
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let report = audit(&secret);
+        assert!(report.blocks.iter().all(|block| block.distinct == 256));
+    }
+}
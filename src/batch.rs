@@ -0,0 +1,194 @@
+//! Bulk key generation.
+//!
+//! Every issued seed is recorded in a `Registry` file before the keys
+//! are handed out, so batches never share seeds. The file is locked
+//! while seeds are reserved, so batches can run in parallel.
+
+use rand_core::RngCore;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use journal;
+use payload::{date, now};
+use {Key, Secret, Seed};
+
+/// Persisted set of issued seeds, one hexadecimal seed per line.
+/// Seeds with the payload marker are written as unsigned numbers.
+#[derive(Debug)]
+pub struct Registry {
+    path: PathBuf,
+    seeds: BTreeSet<Seed>,
+}
+
+impl Registry {
+    /// Opens a registry, a missing file is an empty registry.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let seeds = read_seeds(&path)?;
+        Ok(Registry { path, seeds })
+    }
+
+    pub fn contains(&self, seed: Seed) -> bool {
+        self.seeds.contains(&seed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.seeds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seeds.is_empty()
+    }
+
+    /// Records the seeds. Fails with `AlreadyExists` and records nothing
+    /// if any of them was issued before or repeats, also by another
+    /// process since the registry was opened.
+    pub fn reserve(&mut self, seeds: &[Seed]) -> io::Result<()> {
+        let mut file = journal::lock(&self.path)?;
+        self.seeds = read_seeds(&self.path)?;
+        let mut fresh = BTreeSet::new();
+        for &seed in seeds {
+            if self.seeds.contains(&seed) || !fresh.insert(seed) {
+                let reason = format!("seed {:X} is already issued", seed);
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, reason));
+            }
+        }
+        let lines: Vec<String> = seeds.iter().map(|seed| format!("{:X}", seed)).collect();
+        journal::append(&mut file, &lines)?;
+        self.seeds.extend(fresh);
+        Ok(())
+    }
+
+    /// Picks random non-zero 32-bit seeds which aren't issued yet.
+    /// Fails with `InvalidInput` if fewer than `count` of them are left.
+    pub fn random_seeds<R: RngCore + ?Sized>(&self, count: usize, rng: &mut R) -> io::Result<Vec<Seed>> {
+        let issued = self.seeds.range(1..=u32::MAX as Seed).count();
+        if count > u32::MAX as usize - issued {
+            let reason = format!("only {} random seeds are left", u32::MAX as usize - issued);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+        }
+        let mut seeds = BTreeSet::new();
+        while seeds.len() < count {
+            let seed = rng.next_u32() as Seed;
+            if seed != 0 && !self.seeds.contains(&seed) {
+                seeds.insert(seed);
+            }
+        }
+        Ok(seeds.into_iter().collect())
+    }
+}
+
+fn read_seeds(path: &Path) -> io::Result<BTreeSet<Seed>> {
+    journal::read_lines(path)?.iter()
+        .map(|line| {
+            u64::from_str_radix(line, 16)
+                .map(|seed| seed as Seed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Issued {
+    pub seed: Seed,
+    pub key: Key,
+    /// Seconds since the Unix epoch.
+    pub issued_at: u64,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+/// Reserves the seeds in the registry and generates their keys.
+pub fn issue(secret: &Secret, seeds: &[Seed], registry: &mut Registry) -> io::Result<Vec<Issued>> {
+    registry.reserve(seeds)?;
+    let issued_at = now();
+    let issued = seeds.iter().map(|&seed| {
+        Issued {
+            seed,
+            key: Key::new(seed, secret),
+            issued_at,
+        }
+    }).collect();
+    Ok(issued)
+}
+
+pub fn write<W: Write>(mut out: W, issued: &[Issued], format: Format) -> io::Result<()> {
+    if format == Format::Csv {
+        writeln!(out, "seed,key,issued_at")?;
+    }
+    for item in issued {
+        let issued_at = timestamp(item.issued_at);
+        match format {
+            Format::Csv => {
+                writeln!(out, "{:X},{},{}", item.seed, item.key, issued_at)?;
+            }
+            Format::JsonLines => {
+                writeln!(out, "{{\"seed\":\"{:X}\",\"key\":\"{}\",\"issued_at\":\"{}\"}}",
+                         item.seed, item.key, issued_at)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats seconds since the Unix epoch as RFC 3339 in UTC.
+fn timestamp(secs: u64) -> String {
+    let (year, month, day) = date((secs / 86_400) as u16);
+    let secs = secs % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate rand_chacha;
+
+    use self::rand_chacha::ChaCha8Rng;
+    use rand_core::SeedableRng;
+    use super::*;
+    use std::str::FromStr;
+    use testing::TempPath;
+
+    #[test]
+    fn test_batch() {
+        let path = TempPath::new("registry");
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let mut registry = Registry::open(&path).unwrap();
+        let issued = issue(&secret, &[123, 124], &mut registry).unwrap();
+        assert_eq!(issued[0].key.to_string(), "007B-BFBF-3049-E324");
+
+        let mut registry = Registry::open(&path).unwrap();
+        assert_eq!(registry.len(), 2);
+        // Seeds reserved by another process since the registry was opened
+        Registry::open(&path).unwrap().reserve(&[125]).unwrap();
+        let err = registry.reserve(&[125]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = issue(&secret, &[100, 124], &mut registry).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(!registry.contains(100));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let seeds = registry.random_seeds(10, &mut rng).unwrap();
+        let err = registry.random_seeds(u32::MAX as usize, &mut rng).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let issued = issue(&secret, &seeds, &mut registry).unwrap();
+        assert_eq!(registry.len(), 13);
+        registry.reserve(&[-1]).unwrap();
+        assert!(Registry::open(&path).unwrap().contains(-1));
+
+        let mut out = Vec::new();
+        write(&mut out, &issued[..1], Format::JsonLines).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with(&format!("{{\"seed\":\"{:X}\",\"key\":\"{}\"", seeds[0], issued[0].key)));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(951_782_400 + 3661), "2000-02-29T01:01:01Z");
+    }
+}
@@ -0,0 +1,20 @@
+//! Helpers shared by the binaries.
+
+use serial_number::Secret;
+use std::env;
+use std::fs;
+
+pub const SECRET_VAR: &str = "SERIAL_NUMBER_SECRET";
+
+/// Reads the secret from the file or from `SECRET_VAR`.
+pub fn read_secret(path: Option<&str>) -> Result<Secret, String> {
+    let text = match path {
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?
+        }
+        None => {
+            env::var(SECRET_VAR).map_err(|_| format!("no --secret-file and no {}", SECRET_VAR))?
+        }
+    };
+    text.trim().parse().map_err(|e| format!("invalid secret: {}", e))
+}
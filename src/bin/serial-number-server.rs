@@ -0,0 +1,107 @@
+//! Reference activation server.
+
+extern crate rand_core;
+extern crate serial_number;
+extern crate tiny_http;
+
+mod common;
+
+use common::read_secret;
+use rand_core::{OsRng, RngCore};
+use serial_number::activation::server::{self, Authority, Store};
+use serial_number::hex::{from_hex, to_hex};
+use serial_number::signed::SigningKey;
+use serial_number::{SystemClock, Verifier};
+use std::convert::TryFrom;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::process;
+use tiny_http::Server;
+
+const USAGE: &str = "\
+Usage:
+    serial-number-server [--secret-file FILE] --signing-key FILE --store FILE
+                         [--limit N] [--listen ADDR]
+    serial-number-server --generate-key FILE
+
+The secret is read from --secret-file or the SERIAL_NUMBER_SECRET variable.
+The signing key file holds 32 bytes in hex, --generate-key creates one
+and prints the public key which clients need to check tokens.
+Activations are recorded in the store file, a key can be activated on
+--limit machines, 1 by default.";
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(reason) = run(&args) {
+        eprintln!("error: {}", reason);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut secret_file = None;
+    let mut signing_key = None;
+    let mut store = None;
+    let mut limit = 1;
+    let mut listen = DEFAULT_LISTEN;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--generate-key" {
+            let path = args.next().ok_or("--generate-key needs a path")?;
+            return generate_key(path);
+        } else if arg == "--secret-file" {
+            secret_file = Some(args.next().ok_or("--secret-file needs a path")?);
+        } else if arg == "--signing-key" {
+            signing_key = Some(args.next().ok_or("--signing-key needs a path")?);
+        } else if arg == "--store" {
+            store = Some(args.next().ok_or("--store needs a path")?);
+        } else if arg == "--limit" {
+            let value = args.next().ok_or("--limit needs a number")?;
+            limit = value.parse().map_err(|_| format!("invalid limit: {}", value))?;
+        } else if arg == "--listen" {
+            listen = args.next().ok_or("--listen needs an address")?;
+        } else {
+            return Err(USAGE.to_string());
+        }
+    }
+    let signing_key = signing_key.ok_or(USAGE)?;
+    let store = store.ok_or(USAGE)?;
+    let secret = read_secret(secret_file.map(String::as_str))?;
+    let signing_key = read_signing_key(signing_key)?;
+    let store = Store::open(store).map_err(|e| format!("can't open {}: {}", store, e))?;
+    let verifier = Verifier::new(secret).with_clock(SystemClock);
+    let mut authority = Authority::new(verifier, signing_key, store).with_limit(limit);
+    let server = Server::http(listen).map_err(|e| format!("can't listen on {}: {}", listen, e))?;
+    eprintln!("listening on {}", listen);
+    server::serve(&server, &mut authority);
+    Ok(())
+}
+
+fn generate_key(path: &str) -> Result<(), String> {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let signing_key = SigningKey::from_bytes(&bytes);
+    // Never overwrite a key and keep it readable by the owner only
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)
+        .and_then(|mut file| writeln!(file, "{}", to_hex(&signing_key.to_bytes())))
+        .map_err(|e| format!("can't write {}: {}", path, e))?;
+    println!("{}", to_hex(signing_key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+fn read_signing_key(path: &str) -> Result<SigningKey, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let bytes = from_hex(text.trim())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| format!("invalid signing key in {}", path))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
@@ -0,0 +1,340 @@
+//! Command-line tool to mint, verify and inspect keys.
+
+extern crate rand_core;
+extern crate serial_number;
+
+mod common;
+
+use common::read_secret;
+use rand_core::OsRng;
+use serial_number::batch::{self, Format, Registry};
+use serial_number::hex::from_hex;
+use serial_number::offline::{Challenge, Response};
+use serial_number::payload::format_date;
+use serial_number::revocation::RevocationList;
+use serial_number::signed::VerifyingKey;
+use serial_number::{Blacklist, Identity, Key, KeyStatus, Secret, Seed, SystemClock, Verifier, MAX_GROUPS};
+use std::convert::TryFrom;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::process;
+
+const USAGE: &str = "\
+Usage:
+    serial-number keygen [--secret-file FILE] SEED|START..END|START..=END...
+    serial-number secret [--groups N]
+    serial-number verify [--secret-file FILE] [--identity NAME]
+                         [--revocation-list FILE --public-key KEY] KEY
+    serial-number inspect KEY
+    serial-number respond [--secret-file FILE] [--revocation-list FILE --public-key KEY]
+                          CHALLENGE
+    serial-number batch [--secret-file FILE] --registry FILE [--format csv|json]
+                        [--output FILE] START..END|START..=END|--random COUNT
+
+The secret is read from --secret-file or the SERIAL_NUMBER_SECRET variable.
+Seeds are decimal or hexadecimal with the 0x prefix.
+The registry file records issued seeds, batch never issues a seed twice.
+The revocation list is checked with the public key in hex, verify
+rejects its keys and respond gives them no response.
+respond checks the key of an offline activation challenge and prints
+the response code.
+
+Exit codes of verify:
+    0   the key is valid
+    1   usage or secret error
+    2   the key is malformed or its checksum doesn't match
+    3   the seed is blacklisted
+    4   a group doesn't match the secret, the key is forged
+    5   the key is expired";
+
+const DEFAULT_GROUPS: usize = 4;
+
+struct Failure {
+    code: i32,
+    reason: String,
+}
+
+impl From<String> for Failure {
+    fn from(reason: String) -> Self {
+        Failure { code: 1, reason }
+    }
+}
+
+impl<'a> From<&'a str> for Failure {
+    fn from(reason: &'a str) -> Self {
+        reason.to_string().into()
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(failure) = run(&args) {
+        eprintln!("error: {}", failure.reason);
+        process::exit(failure.code);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    match args.split_first() {
+        Some((command, args)) if command == "keygen" => Ok(keygen(args)?),
+        Some((command, args)) if command == "secret" => Ok(secret(args)?),
+        Some((command, args)) if command == "verify" => verify(args),
+        Some((command, args)) if command == "inspect" => Ok(inspect(args)?),
+        Some((command, args)) if command == "batch" => Ok(batch(args)?),
+        Some((command, args)) if command == "respond" => Ok(respond(args)?),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn keygen(args: &[String]) -> Result<(), String> {
+    let mut secret_file = None;
+    let mut seeds = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--secret-file" {
+            secret_file = Some(args.next().ok_or("--secret-file needs a path")?);
+        } else {
+            seeds.push(arg);
+        }
+    }
+    if seeds.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let secret = read_secret(secret_file.map(String::as_str))?;
+    for arg in seeds {
+        let (start, end) = parse_range(arg)?;
+        for seed in start..=end {
+            println!("{}", Key::new(seed, &secret));
+        }
+    }
+    Ok(())
+}
+
+fn secret(args: &[String]) -> Result<(), String> {
+    let groups = match args {
+        [] => DEFAULT_GROUPS,
+        [flag, groups] if flag == "--groups" => {
+            groups.parse().map_err(|_| format!("invalid number of groups: {}", groups))?
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    if !(1..=MAX_GROUPS).contains(&groups) {
+        return Err(format!("number of groups must be from 1 to {}", MAX_GROUPS));
+    }
+    println!("{}", Secret::generate(groups, &mut OsRng));
+    Ok(())
+}
+
+fn verify(args: &[String]) -> Result<(), Failure> {
+    let mut secret_file = None;
+    let mut identity = None;
+    let mut revocation_list = None;
+    let mut public_key = None;
+    let mut key = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--secret-file" {
+            secret_file = Some(args.next().ok_or("--secret-file needs a path")?);
+        } else if arg == "--identity" {
+            identity = Some(args.next().ok_or("--identity needs a name")?);
+        } else if arg == "--revocation-list" {
+            revocation_list = Some(args.next().ok_or("--revocation-list needs a path")?);
+        } else if arg == "--public-key" {
+            public_key = Some(args.next().ok_or("--public-key needs a key")?);
+        } else if key.is_none() {
+            key = Some(arg);
+        } else {
+            return Err(USAGE.into());
+        }
+    }
+    let key = key.ok_or(USAGE)?;
+    let secret = read_secret(secret_file.map(String::as_str))?;
+    let blacklist = read_blacklist(revocation_list, public_key)?;
+    let key: Key = key.parse().map_err(|e| Failure {
+        code: 2,
+        reason: format!("malformed key: {}", e),
+    })?;
+    let mut verifier = Verifier::new(secret)
+        .with_blacklist(blacklist)
+        .with_clock(SystemClock);
+    if let Some(identity) = identity {
+        verifier = verifier.with_identity(Identity::new(identity));
+    }
+    let (code, reason) = match verifier.verify(&key) {
+        KeyStatus::Valid => {
+            println!("valid");
+            return Ok(());
+        }
+        KeyStatus::Invalid => (2, "invalid checksum or number of groups".to_string()),
+        KeyStatus::Blacklisted => (3, "blacklisted seed".to_string()),
+        KeyStatus::Phony(idx) => (4, format!("group {} doesn't match the secret", idx)),
+        KeyStatus::Expired(day) => (5, format!("expired on {}", format_date(day))),
+    };
+    Err(Failure { code, reason })
+}
+
+fn inspect(args: &[String]) -> Result<(), String> {
+    let key: Key = match args {
+        [key] => key.parse().map_err(|e| format!("malformed key: {}", e))?,
+        _ => return Err(USAGE.to_string()),
+    };
+    let payload = key.payload();
+    println!("seed:     {:04X}", key.seed());
+    println!("serial:   {}", payload.serial);
+    match payload.expires {
+        Some(day) => println!("expires:  {}", format_date(day)),
+        None => println!("expires:  never"),
+    }
+    println!("features: {:04X}", payload.features.bits());
+    for (idx, group) in key.groups().iter().enumerate() {
+        println!("group {}:  {}", idx, group);
+    }
+    let validity = if key.checksum_is_valid() { "valid" } else { "invalid" };
+    println!("checksum: {} ({})", key.checksum(), validity);
+    Ok(())
+}
+
+fn batch(args: &[String]) -> Result<(), String> {
+    let mut secret_file = None;
+    let mut registry = None;
+    let mut format = Format::Csv;
+    let mut output = None;
+    let mut range = None;
+    let mut random = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--secret-file" {
+            secret_file = Some(args.next().ok_or("--secret-file needs a path")?);
+        } else if arg == "--registry" {
+            registry = Some(args.next().ok_or("--registry needs a path")?);
+        } else if arg == "--output" {
+            output = Some(args.next().ok_or("--output needs a path")?);
+        } else if arg == "--format" {
+            format = match args.next().map(String::as_str) {
+                Some("csv") => Format::Csv,
+                Some("json") => Format::JsonLines,
+                _ => return Err("--format needs csv or json".to_string()),
+            };
+        } else if arg == "--random" {
+            let count = args.next().ok_or("--random needs a count")?;
+            random = Some(count.parse::<usize>().map_err(|_| format!("invalid count: {}", count))?);
+        } else if range.is_none() {
+            range = Some(parse_range(arg)?);
+        } else {
+            return Err(USAGE.to_string());
+        }
+    }
+    let registry = registry.ok_or("--registry is required")?;
+    let secret = read_secret(secret_file.map(String::as_str))?;
+    let mut registry = Registry::open(registry).map_err(|e| format!("can't open registry: {}", e))?;
+    let seeds: Vec<Seed> = match (range, random) {
+        (Some((start, end)), None) => (start..=end).collect(),
+        (None, Some(count)) => registry.random_seeds(count, &mut OsRng).map_err(|e| e.to_string())?,
+        _ => return Err(USAGE.to_string()),
+    };
+    // Create the output first, seeds of a failed batch stay free
+    let out: Box<dyn io::Write> = match output {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("can't create {}: {}", path, e))?;
+            Box::new(io::BufWriter::new(file))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    let issued = batch::issue(&secret, &seeds, &mut registry).map_err(|e| e.to_string())?;
+    batch::write(out, &issued, format).map_err(|e| e.to_string())
+}
+
+fn respond(args: &[String]) -> Result<(), String> {
+    let mut secret_file = None;
+    let mut revocation_list = None;
+    let mut public_key = None;
+    let mut challenge = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--secret-file" {
+            secret_file = Some(args.next().ok_or("--secret-file needs a path")?);
+        } else if arg == "--revocation-list" {
+            revocation_list = Some(args.next().ok_or("--revocation-list needs a path")?);
+        } else if arg == "--public-key" {
+            public_key = Some(args.next().ok_or("--public-key needs a key")?);
+        } else if challenge.is_none() {
+            challenge = Some(arg);
+        } else {
+            return Err(USAGE.to_string());
+        }
+    }
+    let challenge = challenge.ok_or(USAGE)?;
+    let challenge: Challenge = challenge.parse().map_err(|e| format!("malformed challenge: {}", e))?;
+    let secret = read_secret(secret_file.map(String::as_str))?;
+    let blacklist = read_blacklist(revocation_list, public_key)?;
+    let verifier = Verifier::new(secret.clone())
+        .with_blacklist(blacklist)
+        .with_clock(SystemClock);
+    let response = Response::new(&challenge, &secret, &verifier)
+        .map_err(|status| format!("no response for the key: {:?}", status))?;
+    println!("{}", response);
+    Ok(())
+}
+
+/// Reads the revocation list and checks it with the public key,
+/// no list is an empty blacklist.
+fn read_blacklist(path: Option<&String>, public_key: Option<&String>) -> Result<Blacklist, String> {
+    let mut blacklist = Blacklist::new();
+    let path = match (path, public_key) {
+        (Some(path), Some(_)) => path,
+        (None, None) => return Ok(blacklist),
+        _ => return Err("--revocation-list and --public-key go together".to_string()),
+    };
+    let public_key = public_key.and_then(|key| from_hex(key))
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or("invalid public key")?;
+    let list = RevocationList::load(path, &public_key)
+        .map_err(|e| format!("can't load {}: {}", path, e))?;
+    blacklist.merge(&list);
+    Ok(blacklist)
+}
+
+fn parse_seed(s: &str) -> Result<Seed, String> {
+    let result = if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16).map(|seed| seed as Seed)
+    } else {
+        s.parse()
+    };
+    result.map_err(|_| format!("invalid seed: {}", s))
+}
+
+/// Parses a single seed or a range of seeds, returns inclusive bounds.
+/// Empty ranges are rejected.
+fn parse_range(s: &str) -> Result<(Seed, Seed), String> {
+    let (start, end) = if let Some(idx) = s.find("..=") {
+        (parse_seed(&s[..idx])?, parse_seed(&s[idx + 3..])?)
+    } else if let Some(idx) = s.find("..") {
+        let end = parse_seed(&s[idx + 2..])?;
+        (parse_seed(&s[..idx])?, end.checked_sub(1).ok_or_else(|| format!("empty range: {}", s))?)
+    } else {
+        let seed = parse_seed(s)?;
+        (seed, seed)
+    };
+    if start > end {
+        return Err(format!("empty range: {}", s));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("0x7B"), Ok((123, 123)));
+        assert_eq!(parse_range("10..20"), Ok((10, 19)));
+        assert_eq!(parse_range("10..=0x14"), Ok((10, 20)));
+        assert!(parse_range("10..x").is_err());
+        assert!(parse_range("10..5").is_err());
+        assert!(parse_range("10..10").is_err());
+        assert!(parse_range("0..-9223372036854775808").is_err());
+    }
+}
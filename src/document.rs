@@ -0,0 +1,34 @@
+//! Text documents with a header line and `name: value` lines, which end
+//! with a line that protects them, e.g. a signature or a MAC.
+
+use std::vec::Vec;
+use Error;
+
+/// Lines of a document without its header and its last line.
+pub type Fields<'a> = Vec<(&'a str, &'a str)>;
+
+/// Splits a document into its fields and the value of the `last` line,
+/// `None` if there is no such line. Blank lines are skipped, nothing may
+/// follow the `last` line.
+pub fn parse<'a>(text: &'a str, header: &str, last: &str) -> Result<(Fields<'a>, Option<&'a str>), Error> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some(header) {
+        return Err(Error::InvalidFormat);
+    }
+    let mut fields = Vec::new();
+    let mut tail = None;
+    for line in lines {
+        if tail.is_some() {
+            return Err(Error::InvalidFormat);
+        }
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let value = parts.next().ok_or(Error::InvalidFormat)?.trim();
+        if name == last {
+            tail = Some(value);
+        } else {
+            fields.push((name, value));
+        }
+    }
+    Ok((fields, tail))
+}
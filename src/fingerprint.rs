@@ -0,0 +1,395 @@
+//! Machine fingerprints for node-locked keys.
+//!
+//! The fingerprint is collected from Linux sources:
+//!
+//! * `/etc/machine-id`, or `/var/lib/dbus/machine-id` on older systems
+//! * `/sys/class/dmi/id/product_uuid`, which is readable by root only
+//! * MAC addresses of the physical network interfaces
+//! * UUID of the root filesystem
+//!
+//! Missing or unreadable sources are skipped. The components are hashed
+//! into a `MachineId`, which can be passed to `Key::with_identity` or used
+//! as the fingerprint of an activation.
+//!
+//! A `MachineId` changes with any component. To survive the replacement
+//! of a NIC or a disk, store the `Profile` of the machine instead and
+//! accept it when enough of its components still match:
+//!
+//! ```text
+//! M5E0C2B41-U0F9A7733-N8D41E6A0-R2C7719FE
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str;
+use identity::fnv1a;
+use {mix, Error, Identity};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+pub enum Source {
+    MachineId,
+    ProductUuid,
+    Mac,
+    RootFs,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Hash)]
+pub struct Component {
+    pub source: Source,
+    pub value: String,
+}
+
+/// Components of a machine, sorted.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Fingerprint {
+    components: Vec<Component>,
+}
+
+impl Fingerprint {
+    pub fn new(mut components: Vec<Component>) -> Self {
+        components.sort();
+        components.dedup();
+        Fingerprint { components }
+    }
+
+    /// Collects the fingerprint of this machine.
+    pub fn collect() -> io::Result<Self> {
+        Fingerprint::collect_from("/")
+    }
+
+    /// Collects the fingerprint from a file system tree mounted at `root`.
+    /// Fails if none of the sources can be read.
+    pub fn collect_from<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref();
+        let mut components = Vec::new();
+        let mut machine_id = read_value(&root.join("etc/machine-id"))?;
+        if machine_id.is_none() {
+            machine_id = read_value(&root.join("var/lib/dbus/machine-id"))?;
+        }
+        if let Some(value) = machine_id {
+            components.push(Component { source: Source::MachineId, value });
+        }
+        if let Some(value) = read_value(&root.join("sys/class/dmi/id/product_uuid"))? {
+            components.push(Component { source: Source::ProductUuid, value });
+        }
+        for value in macs(root)? {
+            components.push(Component { source: Source::Mac, value });
+        }
+        if let Some(value) = root_uuid(root)? {
+            components.push(Component { source: Source::RootFs, value });
+        }
+        if components.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no fingerprint sources"));
+        }
+        Ok(Fingerprint::new(components))
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    pub fn id(&self) -> MachineId {
+        let mut bytes = Vec::new();
+        for component in &self.components {
+            bytes.push(component.source as u8);
+            bytes.extend_from_slice(component.value.as_bytes());
+            bytes.push(0);
+        }
+        MachineId(mix(fnv1a(&bytes)))
+    }
+
+    pub fn profile(&self) -> Profile {
+        Profile {
+            components: self.components.iter().map(ComponentId::new).collect(),
+        }
+    }
+}
+
+/// Hash of a single component.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+pub struct ComponentId {
+    pub source: Source,
+    hash: u32,
+}
+
+impl ComponentId {
+    fn new(component: &Component) -> Self {
+        let mut bytes = vec![component.source as u8];
+        bytes.extend_from_slice(component.value.as_bytes());
+        ComponentId {
+            source: component.source,
+            hash: mix(fnv1a(&bytes)) as u32,
+        }
+    }
+}
+
+impl fmt::Display for ComponentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let letter = match self.source {
+            Source::MachineId => 'M',
+            Source::ProductUuid => 'U',
+            Source::Mac => 'N',
+            Source::RootFs => 'R',
+        };
+        write!(f, "{}{:08X}", letter, self.hash)
+    }
+}
+
+impl str::FromStr for ComponentId {
+    type Err = Error;
+
+    fn from_str(fragment: &str) -> Result<Self, Self::Err> {
+        if fragment.len() != 9 || !fragment.is_char_boundary(1) {
+            return Err(Error::InvalidFragment);
+        }
+        let source = match &fragment[..1] {
+            "M" => Source::MachineId,
+            "U" => Source::ProductUuid,
+            "N" => Source::Mac,
+            "R" => Source::RootFs,
+            _ => return Err(Error::InvalidFragment),
+        };
+        Ok(ComponentId {
+            source,
+            hash: u32::from_str_radix(&fragment[1..], 16)?,
+        })
+    }
+}
+
+/// Hashed components of a machine, recorded when a key is activated.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Profile {
+    components: Vec<ComponentId>,
+}
+
+impl Profile {
+    pub fn components(&self) -> &[ComponentId] {
+        &self.components
+    }
+
+    /// Compares the recorded components with the current fingerprint.
+    /// The machine matches if at least `required` of them are still there,
+    /// components which were added since don't count.
+    pub fn check(&self, current: &Fingerprint, required: usize) -> MatchReport {
+        let current = current.profile();
+        let drifted: Vec<ComponentId> = self.components.iter()
+            .filter(|component| !current.components.contains(component))
+            .cloned()
+            .collect();
+        MatchReport {
+            matched: self.components.len() - drifted.len(),
+            required,
+            drifted,
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, component) in self.components.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", component)?;
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = Vec::new();
+        for fragment in s.split('-') {
+            components.push(fragment.parse()?);
+        }
+        Ok(Profile { components })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MatchReport {
+    pub matched: usize,
+    pub required: usize,
+    /// Recorded components which are missing or changed.
+    pub drifted: Vec<ComponentId>,
+}
+
+impl MatchReport {
+    pub fn passed(&self) -> bool {
+        self.matched >= self.required
+    }
+}
+
+/// Hash of a fingerprint, written as four groups of hex digits.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct MachineId(u64);
+
+impl MachineId {
+    /// Identity to bind keys to this machine.
+    pub fn identity(&self) -> Identity {
+        Identity(self.0)
+    }
+}
+
+impl fmt::Display for MachineId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X}-{:04X}-{:04X}-{:04X}",
+               self.0 >> 48, self.0 >> 32 & 0xFFFF, self.0 >> 16 & 0xFFFF, self.0 & 0xFFFF)
+    }
+}
+
+impl str::FromStr for MachineId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items: Vec<&str> = s.split('-').collect();
+        if items.len() != 4 {
+            return Err(Error::NotEnoughItems);
+        }
+        let mut id = 0;
+        for item in items {
+            if item.len() != 4 {
+                return Err(Error::InvalidFragment);
+            }
+            id = id << 16 | u64::from_str_radix(item, 16)?;
+        }
+        Ok(MachineId(id))
+    }
+}
+
+/// Reads a trimmed, lowercase value, `None` if it's missing, empty
+/// or not readable by this user.
+fn read_value(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let value = text.trim().to_lowercase();
+            Ok(if value.is_empty() { None } else { Some(value) })
+        }
+        Err(ref e) if skippable(e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn skippable(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::NotFound || e.kind() == io::ErrorKind::PermissionDenied
+}
+
+/// MAC addresses of interfaces backed by a device, virtual interfaces
+/// like bridges and tunnels have no `device` link.
+fn macs(root: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(root.join("sys/class/net")) {
+        Ok(entries) => entries,
+        Err(ref e) if skippable(e) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut macs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.join("device").exists() {
+            continue;
+        }
+        if let Some(mac) = read_value(&path.join("address"))? {
+            if mac.chars().any(|c| c != '0' && c != ':') {
+                macs.push(mac);
+            }
+        }
+    }
+    Ok(macs)
+}
+
+/// Finds the device mounted at `/` and its link in `/dev/disk/by-uuid`.
+fn root_uuid(root: &Path) -> io::Result<Option<String>> {
+    let mounts = match fs::read_to_string(root.join("proc/self/mounts")) {
+        Ok(mounts) => mounts,
+        Err(ref e) if skippable(e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let device = mounts.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.len() > 1 && fields[1] == "/")
+        .map(|fields| PathBuf::from(fields[0]));
+    let device = match device.as_ref().and_then(|device| device.file_name()) {
+        Some(name) => name.to_owned(),
+        None => return Ok(None),
+    };
+    let entries = match fs::read_dir(root.join("dev/disk/by-uuid")) {
+        Ok(entries) => entries,
+        Err(ref e) if skippable(e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let target = match fs::read_link(entry.path()) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if target.file_name() == Some(&device) {
+            return Ok(Some(entry.file_name().to_string_lossy().to_lowercase()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::str::FromStr;
+    use {Key, Secret};
+    use testing::TempPath;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_collect() {
+        let root = TempPath::new("fingerprint");
+        write(&root, "etc/machine-id", "4C4C4544004A4B10\n");
+        write(&root, "sys/class/dmi/id/product_uuid", "4C4C4544-004A-4B10-8052-B4C04F4B4E32\n");
+        write(&root, "sys/class/net/eth0/address", "52:54:00:12:34:56\n");
+        fs::create_dir_all(root.join("sys/class/net/eth0/device")).unwrap();
+        write(&root, "sys/class/net/lo/address", "00:00:00:00:00:00\n");
+        write(&root, "sys/class/net/docker0/address", "02:42:ac:11:00:02\n");
+        write(&root, "proc/self/mounts", "proc /proc proc rw 0 0\n/dev/nvme0n1p2 / ext4 rw 0 0\n");
+        fs::create_dir_all(root.join("dev/disk/by-uuid")).unwrap();
+        symlink("../../nvme0n1p1", root.join("dev/disk/by-uuid/0A1B-2C3D")).unwrap();
+        symlink("../../nvme0n1p2", root.join("dev/disk/by-uuid/9f1c2d3e-0000-4000-8000-1234567890ab")).unwrap();
+
+        let fingerprint = Fingerprint::collect_from(&root).unwrap();
+        let values: Vec<&str> = fingerprint.components().iter().map(|c| c.value.as_str()).collect();
+        assert_eq!(values, vec![
+            "4c4c4544004a4b10",
+            "4c4c4544-004a-4b10-8052-b4c04f4b4e32",
+            "52:54:00:12:34:56",
+            "9f1c2d3e-0000-4000-8000-1234567890ab",
+        ]);
+        let id = fingerprint.id();
+        assert_eq!(MachineId::from_str(&id.to_string()).unwrap(), id);
+
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let key = Key::with_identity(123, &id.identity(), &secret);
+        assert!(key.valid_for(&secret, &Fingerprint::collect_from(&root).unwrap().id().identity()));
+
+        let profile = fingerprint.profile();
+        assert_eq!(Profile::from_str(&profile.to_string()).unwrap(), profile);
+
+        write(&root, "sys/class/net/eth0/address", "52:54:00:65:43:21\n");
+        let current = Fingerprint::collect_from(&root).unwrap();
+        assert_ne!(current.id(), id);
+        let report = profile.check(&current, 3);
+        assert!(report.passed());
+        assert_eq!(report.matched, 3);
+        assert_eq!(report.drifted.len(), 1);
+        assert_eq!(report.drifted[0].source, Source::Mac);
+        assert!(!profile.check(&current, 4).passed());
+        fs::remove_dir_all(&root).unwrap();
+        assert!(Fingerprint::collect_from(&root).is_err());
+    }
+}
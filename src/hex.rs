@@ -0,0 +1,14 @@
+//! Hexadecimal encoding of binary data in files and protocols.
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|idx| u8::from_str_radix(&s[idx..idx + 2], 16).ok())
+        .collect()
+}
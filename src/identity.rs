@@ -0,0 +1,41 @@
+//! Binding keys to a registered customer name or email.
+
+use alloc::vec::Vec;
+
+/// Normalized and hashed identity which a key is bound to.
+///
+/// Letter case and whitespace around and between words are ignored,
+/// so `" John  Smith "` and `"john smith"` are the same identity.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct Identity(pub(crate) u64);
+
+impl Identity {
+    pub fn new(identity: &str) -> Self {
+        let normalized = identity.split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Identity(fnv1a(normalized.as_bytes()))
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Identity::new(" John  Smith\n"), Identity::new("john smith"));
+        assert_eq!(Identity::new("User@Example.COM"), Identity::new("user@example.com"));
+        assert_ne!(Identity::new("john smith"), Identity::new("johnsmith"));
+    }
+}
@@ -2,15 +2,81 @@
 //!
 //! [Source 1](http://www.brandonstaggs.com/2007/07/26/implementing-a-partial-serial-number-verification-system-in-delphi/)
 //! [Source 2](https://github.com/garethrbrown/.net-licence-key-generator/blob/master/AppSoftware.LicenceEngine.KeyGenerator/PkvLicenceKeyGenerator.cs)
-
-
-use std::fmt;
-use std::mem;
-use std::str;
-use std::u8;
-use std::i64;
-use std::num;
-use std::error;
+//!
+//! Without the default `std` feature the crate needs only `alloc`, so keys
+//! can be generated and checked on embedded targets.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "signed")]
+extern crate ed25519_dalek;
+#[cfg(feature = "hmac")]
+extern crate hmac;
+extern crate rand_core;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "sha2")]
+extern crate sha2;
+#[cfg(feature = "tiny_http")]
+extern crate tiny_http;
+#[cfg(feature = "ureq")]
+extern crate ureq;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+use core::iter::FromIterator;
+use core::num;
+use core::str;
+use rand_core::RngCore;
+
+pub mod analysis;
+#[cfg(feature = "anchor")]
+pub mod anchor;
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod fingerprint;
+pub mod identity;
+pub mod offline;
+pub mod payload;
+
+#[cfg(feature = "activation")]
+pub mod activation;
+#[cfg(any(feature = "signed", feature = "anchor"))]
+mod document;
+#[cfg(any(feature = "license", feature = "signed", feature = "anchor"))]
+pub mod hex;
+#[cfg(feature = "std")]
+mod journal;
+#[cfg(feature = "license")]
+pub mod license;
+#[cfg(any(feature = "license", feature = "anchor"))]
+mod mac;
+#[cfg(feature = "signed")]
+pub mod revocation;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "signed")]
+pub mod signed;
+#[cfg(feature = "trial")]
+pub mod trial;
+#[cfg(all(test, feature = "std"))]
+mod testing;
+
+pub use identity::Identity;
+pub use payload::{Clock, Day, Features, Payload};
+#[cfg(feature = "std")]
+pub use payload::SystemClock;
 
 pub type Seed = i64;
 
@@ -21,66 +87,173 @@ pub enum Error {
     NotEnoughItems,
     InvalidFragment,
     InvalidFormat,
+    InvalidChecksum,
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
             Error::NotEnoughItems => "not enough items",
             Error::InvalidFragment => "invalid fragment",
             Error::InvalidFormat => "invalid format",
+            Error::InvalidChecksum => "invalid checksum",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl From<num::ParseIntError> for Error {
+    fn from(_: num::ParseIntError) -> Self {
+        Error::InvalidFormat
+    }
+}
+
+#[derive(Clone)]
+pub struct Secret(Vec<Group<Block>>);
+
+impl str::FromStr for Secret {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut groups = Vec::new();
+        for fragment in s.split('-') {
+            groups.push(fragment.parse()?);
         }
+        Ok(Secret(groups))
     }
+}
 
-    fn cause(&self) -> Option<&error::Error> {
-        None
+/// Largest number of groups in a generated secret, there are only
+/// this many pairs of distinct blocks which aren't weak.
+pub const MAX_GROUPS: usize = 17_136;
+
+impl Secret {
+    /// Generates a secret with the given number of groups.
+    /// Weak and duplicate blocks are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `groups` is `0` or greater than `MAX_GROUPS`.
+    pub fn generate<R: RngCore + ?Sized>(groups: usize, rng: &mut R) -> Self {
+        assert!((1..=MAX_GROUPS).contains(&groups),
+                "number of groups must be from 1 to {}", MAX_GROUPS);
+        let mut blocks: Vec<Block> = Vec::new();
+        while blocks.len() < groups * 2 {
+            let mut bytes = [0; 3];
+            rng.fill_bytes(&mut bytes);
+            let block = Block::new(bytes[0], bytes[1], bytes[2]);
+            if block.is_weak() || blocks.iter().any(|b| b.params() == block.params()) {
+                continue;
+            }
+            blocks.push(block);
+        }
+        let groups = blocks.chunks(2).map(|pair| {
+            Group {
+                left: pair[0].clone(),
+                right: pair[1].clone(),
+            }
+        }).collect();
+        Secret(groups)
+    }
+
+    /// Keeps only the groups with the given indices. The result can be
+    /// embedded into a release instead of the full secret.
+    pub fn partial(&self, known: &[usize]) -> PartialSecret {
+        let groups = self.0.iter().enumerate().map(|(idx, group)| {
+            if known.contains(&idx) {
+                Some(group.clone())
+            } else {
+                None
+            }
+        }).collect();
+        PartialSecret(groups)
     }
 }
 
-impl fmt::Display for Error {
+/// Writes the secret in the same format which `from_str` reads.
+impl fmt::Display for Secret {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use std::error::Error;
-
-        write!(f, "{}", self.description())
+        for (idx, group) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", group)?;
+        }
+        Ok(())
     }
 }
 
-impl From<num::ParseIntError> for Error {
-    fn from(_: num::ParseIntError) -> Self {
-        Error::InvalidFormat
+/// Never reveals the groups.
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret({} groups)", self.0.len())
     }
 }
 
+/// A secret where some of the groups are unknown.
+///
+/// Unknown groups are written as `?` in the string form,
+/// e.g. `0A6BBFAA6793-?`.
 #[derive(Clone)]
-pub struct Secret(Vec<Group<Block>>);
+pub struct PartialSecret(Vec<Option<Group<Block>>>);
+
+impl PartialSecret {
+    /// Indices of the groups which are present and will be checked.
+    pub fn known(&self) -> Vec<usize> {
+        self.0.iter()
+            .enumerate()
+            .filter(|&(_, group)| group.is_some())
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
 
-impl str::FromStr for Secret {
+impl From<Secret> for PartialSecret {
+    fn from(secret: Secret) -> Self {
+        PartialSecret(secret.0.into_iter().map(Some).collect())
+    }
+}
+
+impl fmt::Display for PartialSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, group) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "-")?;
+            }
+            match *group {
+                Some(ref group) => write!(f, "{}", group)?,
+                None => write!(f, "?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PartialSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PartialSecret({} groups, known: {:?})", self.0.len(), self.known())
+    }
+}
+
+impl str::FromStr for PartialSecret {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let fragments: Vec<&str> = s.split("-").collect();
         let mut groups = Vec::new();
-        for fragment in fragments {
-            if fragment.len() != 12 {
-                return Err(Error::InvalidFragment);
+        for fragment in s.split('-') {
+            if fragment == "?" {
+                groups.push(None);
+            } else {
+                groups.push(Some(fragment.parse()?));
             }
-            let left_a = try!(u8::from_str_radix(&fragment[0..2], 16));
-            let left_b = try!(u8::from_str_radix(&fragment[2..4], 16));
-            let left_c = try!(u8::from_str_radix(&fragment[4..6], 16));
-            let right_a = try!(u8::from_str_radix(&fragment[6..8], 16));
-            let right_b = try!(u8::from_str_radix(&fragment[8..10], 16));
-            let right_c = try!(u8::from_str_radix(&fragment[10..12], 16));
-            let group = Group {
-                left: Block::new(left_a, left_b, left_c),
-                right: Block::new(right_a, right_b, right_c),
-            };
-            groups.push(group);
         }
-        Ok(Secret(groups))
+        Ok(PartialSecret(groups))
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Key {
     seed: Seed,
     groups: Vec<Group<Byte>>,
@@ -88,27 +261,239 @@ pub struct Key {
 }
 
 impl Key {
-    pub fn new(seed: Seed, &Secret(ref blocks): &Secret) -> Self {
-        let groups: Vec<Group<Byte>> = blocks.iter().map(|g| g.produce(seed)).collect();
+    pub fn new(seed: Seed, secret: &Secret) -> Self {
+        Key::generate(seed, None, secret)
+    }
+
+    /// Creates a key which is valid only for the given identity.
+    pub fn with_identity(seed: Seed, identity: &Identity, secret: &Secret) -> Self {
+        Key::generate(seed, Some(identity), secret)
+    }
+
+    fn generate(seed: Seed, identity: Option<&Identity>, secret: &Secret) -> Self {
+        let derived = derive(seed, identity);
+        let groups: Vec<Group<Byte>> = secret.0.iter().map(|g| g.produce(derived)).collect();
         let checksum = checksum(seed, &groups);
         Key {
-            seed: seed,
-            groups: groups,
-            checksum: checksum,
+            seed,
+            groups,
+            checksum,
         }
     }
 
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    pub fn groups(&self) -> &[Group<Byte>] {
+        &self.groups
+    }
+
+    pub fn checksum(&self) -> &Group<Byte> {
+        &self.checksum
+    }
+
+    pub fn payload(&self) -> Payload {
+        Payload::from_seed(self.seed)
+    }
+
+    /// Features granted by the key. Only trust it after the key is verified.
+    pub fn features(&self) -> Features {
+        self.payload().features
+    }
+
+    pub fn grants(&self, features: Features) -> bool {
+        self.features().contains(features)
+    }
+
+    /// Parses a key and rejects it if the checksum doesn't match.
+    pub fn parse_strict(s: &str) -> Result<Self, Error> {
+        let key: Key = s.parse()?;
+        if key.checksum_is_valid() {
+            Ok(key)
+        } else {
+            Err(Error::InvalidChecksum)
+        }
+    }
+
+    /// Checks the checksum only. It doesn't need any secret, so it's safe
+    /// to use it to catch typos in the UI.
+    pub fn checksum_is_valid(&self) -> bool {
+        checksum(self.seed, &self.groups) == self.checksum
+    }
+
     pub fn valid(&self, secret: &Secret) -> bool {
-        let valid_key = Key::new(self.seed, secret);
-        self == &valid_key
+        self.status(secret).is_valid()
+    }
+
+    /// Checks a key which was created with `Key::with_identity`.
+    pub fn valid_for(&self, secret: &Secret, identity: &Identity) -> bool {
+        self.check(secret.0.iter().map(Some), Some(identity), &Blacklist::new()).is_valid()
+    }
+
+    /// Checks the key against the full secret and tells which step failed.
+    pub fn status(&self, secret: &Secret) -> KeyStatus {
+        self.check(secret.0.iter().map(Some), None, &Blacklist::new())
+    }
+
+    fn check<'a, I>(&self, blocks: I, identity: Option<&Identity>, blacklist: &Blacklist) -> KeyStatus
+        where I: ExactSizeIterator<Item = Option<&'a Group<Block>>>
+    {
+        if self.groups.len() != blocks.len() {
+            return KeyStatus::Invalid;
+        }
+        if !self.checksum_is_valid() {
+            return KeyStatus::Invalid;
+        }
+        if blacklist.contains(self.seed) {
+            return KeyStatus::Blacklisted;
+        }
+        let derived = derive(self.seed, identity);
+        for (idx, (block, group)) in blocks.zip(&self.groups).enumerate() {
+            if let Some(block) = block {
+                if &block.produce(derived) != group {
+                    return KeyStatus::Phony(idx);
+                }
+            }
+        }
+        KeyStatus::Valid
+    }
+}
+
+/// Result of a key verification.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum KeyStatus {
+    Valid,
+    /// The key has a wrong number of groups or its checksum doesn't match.
+    /// Usually it's a typo.
+    Invalid,
+    /// The seed of the key is blacklisted.
+    Blacklisted,
+    /// The checksum is correct, but the group with this index wasn't
+    /// produced by the secret. The key is forged.
+    Phony(usize),
+    /// The key is genuine, but expired on this day.
+    Expired(Day),
+}
+
+impl KeyStatus {
+    pub fn is_valid(&self) -> bool {
+        *self == KeyStatus::Valid
+    }
+}
+
+/// Seeds of leaked or charged-back keys which must be rejected.
+#[derive(Clone, Default, Debug)]
+pub struct Blacklist(BTreeSet<Seed>);
+
+impl Blacklist {
+    pub fn new() -> Self {
+        Blacklist::default()
+    }
+
+    pub fn insert(&mut self, seed: Seed) -> bool {
+        self.0.insert(seed)
+    }
+
+    pub fn contains(&self, seed: Seed) -> bool {
+        self.0.contains(&seed)
+    }
+}
+
+impl FromIterator<Seed> for Blacklist {
+    fn from_iter<I: IntoIterator<Item = Seed>>(iter: I) -> Self {
+        Blacklist(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Seed> for Blacklist {
+    fn extend<I: IntoIterator<Item = Seed>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+/// Partial key verification.
+///
+/// Checks the checksum of a key and only the groups which are known
+/// to the `PartialSecret`. Different releases should hold back different
+/// groups, so a secret extracted from one release can't produce keys
+/// which pass the next one.
+pub struct Verifier {
+    secret: PartialSecret,
+    blacklist: Blacklist,
+    identity: Option<Identity>,
+    clock: Option<Box<dyn Clock>>,
+    require_expiry: bool,
+}
+
+impl Verifier {
+    pub fn new<S: Into<PartialSecret>>(secret: S) -> Self {
+        Verifier {
+            secret: secret.into(),
+            blacklist: Blacklist::new(),
+            identity: None,
+            clock: None,
+            require_expiry: false,
+        }
+    }
+
+    /// Rejects keys with blacklisted seeds.
+    pub fn with_blacklist(mut self, blacklist: Blacklist) -> Self {
+        self.blacklist = blacklist;
+        self
+    }
+
+    /// Accepts only keys which were bound to the identity.
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Rejects keys which expired before the current date of the clock.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Rejects keys without an expiry date as `Invalid`.
+    pub fn require_expiry(mut self) -> Self {
+        self.require_expiry = true;
+        self
+    }
+
+    /// Indices of the groups which this verifier checks.
+    pub fn checked(&self) -> Vec<usize> {
+        self.secret.known()
+    }
+
+    pub fn verify(&self, key: &Key) -> KeyStatus {
+        let blocks = self.secret.0.iter().map(Option::as_ref);
+        let status = key.check(blocks, self.identity.as_ref(), &self.blacklist);
+        if status != KeyStatus::Valid {
+            return status;
+        }
+        expiry_status(key.payload(), self.clock.as_deref(), self.require_expiry)
+    }
+}
+
+fn expiry_status(payload: Payload, clock: Option<&dyn Clock>, require_expiry: bool) -> KeyStatus {
+    match payload.expires {
+        Some(expires) => {
+            match clock {
+                Some(clock) if clock.today() > expires => KeyStatus::Expired(expires),
+                _ => KeyStatus::Valid,
+            }
+        }
+        None if require_expiry => KeyStatus::Invalid,
+        None => KeyStatus::Valid,
     }
 }
 
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f, "{:04X}", self.seed));
+        write!(f, "{:04X}", self.seed)?;
         for group in &self.groups {
-            try!(write!(f, "-{}", group));
+            write!(f, "-{}", group)?;
         }
         write!(f, "-{}", self.checksum)
     }
@@ -123,25 +508,17 @@ impl str::FromStr for Key {
             return Err(Error::NotEnoughItems);
         }
         if let Some((seed, tail)) = items.split_first() {
-            let seed = try!(i64::from_str_radix(&seed, 16));
+            // Seeds with the payload marker are negative
+            let seed = u64::from_str_radix(seed, 16)? as Seed;
             let mut groups = Vec::new();
             for fragment in tail {
-                if fragment.len() != 4 {
-                    return Err(Error::InvalidFragment);
-                }
-                let left = try!(u8::from_str_radix(&fragment[0..2], 16));
-                let right = try!(u8::from_str_radix(&fragment[2..4], 16));
-                let group = Group {
-                    left: left,
-                    right: right,
-                };
-                groups.push(group);
+                groups.push(fragment.parse()?);
             }
             let checksum = groups.pop().unwrap();
             let key = Key {
-                seed: seed,
-                groups: groups,
-                checksum: checksum,
+                seed,
+                groups,
+                checksum,
             };
             Ok(key)
         } else {
@@ -157,11 +534,45 @@ pub struct Group<T> {
 }
 
 impl Group<Block> {
-    fn produce(&self, seed: Seed) -> Group<Byte> {
+    fn produce(&self, derived: Derived) -> Group<Byte> {
         Group {
-            left: self.left.produce(seed),
-            right: self.right.produce(seed),
+            left: self.left.produce(derived.value) ^ self.left.mask(derived.domain),
+            right: self.right.produce(derived.value) ^ self.right.mask(derived.domain),
+        }
+    }
+}
+
+impl str::FromStr for Group<Block> {
+    type Err = Error;
+
+    fn from_str(fragment: &str) -> Result<Self, Self::Err> {
+        if fragment.len() != 12 || !fragment.is_char_boundary(6) {
+            return Err(Error::InvalidFragment);
         }
+        Ok(Group {
+            left: fragment[0..6].parse()?,
+            right: fragment[6..12].parse()?,
+        })
+    }
+}
+
+impl str::FromStr for Group<Byte> {
+    type Err = Error;
+
+    fn from_str(fragment: &str) -> Result<Self, Self::Err> {
+        if fragment.len() != 4 || !fragment.is_char_boundary(2) {
+            return Err(Error::InvalidFragment);
+        }
+        Ok(Group {
+            left: u8::from_str_radix(&fragment[0..2], 16)?,
+            right: u8::from_str_radix(&fragment[2..4], 16)?,
+        })
+    }
+}
+
+impl fmt::Display for Group<Block> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.left, self.right)
     }
 }
 
@@ -178,22 +589,115 @@ pub struct Block {
     c: Byte,
 }
 
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X}{:02X}{:02X}", self.a, self.b, self.c)
+    }
+}
+
+impl str::FromStr for Block {
+    type Err = Error;
+
+    fn from_str(fragment: &str) -> Result<Self, Self::Err> {
+        if fragment.len() != 6 || !fragment.is_char_boundary(2) || !fragment.is_char_boundary(4) {
+            return Err(Error::InvalidFragment);
+        }
+        let a = u8::from_str_radix(&fragment[0..2], 16)?;
+        let b = u8::from_str_radix(&fragment[2..4], 16)?;
+        let c = u8::from_str_radix(&fragment[4..6], 16)?;
+        Ok(Block::new(a, b, c))
+    }
+}
+
 impl Block {
 
     pub fn new(a: Byte, b: Byte, c: Byte) -> Self {
         Block {
-            a: a,
-            b: b,
-            c: c,
+            a,
+            b,
+            c,
         }
     }
 
+    /// Weak blocks produce bytes which barely depend on the secret:
+    /// the mask `c` has too few or too many bits set, or both shifts
+    /// are the same and the seed byte cancels itself out.
+    pub fn is_weak(&self) -> bool {
+        let bits = self.c.count_ones();
+        !(2..=6).contains(&bits) || self.a % 25 == self.b % 3
+    }
+
+    /// Only these values affect the output of `produce`.
+    fn params(&self) -> (Byte, bool, Byte, Byte) {
+        (self.a % 25, self.a.is_multiple_of(2), self.b % 3, self.c)
+    }
+
     fn produce(&self, seed: Seed) -> Byte {
         let a = (seed >> (self.a % 25)) as Byte;
         let b = (seed >> (self.b % 3)) as Byte;
-        let c = if self.a % 2 == 0 { b | self.c } else { b & self.c };
+        let c = if self.a.is_multiple_of(2) { b | self.c } else { b & self.c };
         a ^ c
     }
+
+    /// Secret byte which the output is masked with in a domain. It depends
+    /// on all bits of the block, also on those which `produce` ignores.
+    fn mask(&self, domain: Domain) -> Byte {
+        match domain {
+            Domain::Plain => 0,
+            domain => {
+                let bytes = [0, 0, 0, 0, domain as u8, self.a, self.b, self.c];
+                mix(u64::from_be_bytes(bytes)) as Byte
+            }
+        }
+    }
+}
+
+/// Kind of the value which the groups are produced from.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Domain {
+    /// A seed without a payload, used as is.
+    Plain = 0,
+    /// A hashed seed with a payload.
+    Payload = 1,
+    /// A hashed seed bound to an identity.
+    Identity = 2,
+    /// A hashed challenge of offline activation.
+    Response = 3,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Derived {
+    value: Seed,
+    domain: Domain,
+}
+
+/// Seeds without the payload marker and without an identity are used as is
+/// and produce the same keys as before payloads. Blocks read only the lower
+/// 32 bits of a seed, so for other seeds the higher bits and the identity are
+/// hashed into them. Their groups are masked per domain, so a hashed value
+/// never gives the groups of a plain seed, and a key bound to an identity
+/// never gives the groups of a key without one.
+fn derive(seed: Seed, identity: Option<&Identity>) -> Derived {
+    let (salt, domain) = match identity {
+        Some(identity) => (mix(identity.0), Domain::Identity),
+        None if !payload::is_extended(seed) => {
+            return Derived {
+                value: seed,
+                domain: Domain::Plain,
+            };
+        }
+        None => (0, Domain::Payload),
+    };
+    Derived {
+        value: (mix(seed as u64 ^ salt) & 0xFFFF_FFFF) as Seed,
+        domain,
+    }
+}
+
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    hash ^ (hash >> 33)
 }
 
 fn checksum(seed: Seed, groups: &[Group<Byte>]) -> Group<Byte> {
@@ -202,7 +706,7 @@ fn checksum(seed: Seed, groups: &[Group<Byte>]) -> Group<Byte> {
     {
         let mut update = |slice: &[u8]| {
             for byte in slice {
-                right = right + *byte as u16;
+                right += *byte as u16;
 
                 if right > 0xFF {
                     right -= 0xFF;
@@ -215,8 +719,7 @@ fn checksum(seed: Seed, groups: &[Group<Byte>]) -> Group<Byte> {
                 }
             }
         };
-        let bytes: [u8; 8] = unsafe { mem::transmute(seed.to_be()) };
-        update(&bytes);
+        update(&seed.to_be_bytes());
         for item in groups {
             update(&[item.left, item.right]);
         }
@@ -229,6 +732,10 @@ fn checksum(seed: Seed, groups: &[Group<Byte>]) -> Group<Byte> {
 
 #[cfg(test)]
 mod tests {
+    extern crate rand_chacha;
+
+    use self::rand_chacha::ChaCha8Rng;
+    use rand_core::SeedableRng;
     use super::*;
     use std::str::FromStr;
 
@@ -248,4 +755,178 @@ mod tests {
         assert_eq!(&format!("{}", key), "1233-A5B6-4324");
     }
 
+    #[test]
+    fn test_secret_roundtrip() {
+        let text = "0A6BBFAA6793-ABB734930FCD";
+        let secret = Secret::from_str(text).unwrap();
+        assert_eq!(secret.to_string(), text);
+        assert_eq!(format!("{:?}", secret), "Secret(2 groups)");
+        let partial = secret.partial(&[1]);
+        assert_eq!(partial.to_string(), "?-ABB734930FCD");
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let secret = Secret::generate(3, &mut rng);
+        let restored = Secret::from_str(&secret.to_string()).unwrap();
+        assert_eq!(Key::new(77, &secret), Key::new(77, &restored));
+    }
+
+    #[test]
+    fn test_partial() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let verifier = Verifier::new(secret.partial(&[1]));
+        assert_eq!(verifier.checked(), vec![1]);
+        let key = Key::from_str("007B-BFBF-3049-E324").unwrap();
+        assert!(verifier.verify(&key).is_valid());
+        // Tampering with an unchecked group passes, but not with the checked one
+        let partial = PartialSecret::from_str("?-ABB734930FCD").unwrap();
+        let verifier = Verifier::new(partial);
+        let mut forged = key.clone();
+        forged.groups[0] = Group { left: 0, right: 0 };
+        forged.checksum = checksum(forged.seed, &forged.groups);
+        assert!(verifier.verify(&forged).is_valid());
+        let mut forged = key.clone();
+        forged.groups[1] = Group { left: 0, right: 0 };
+        forged.checksum = checksum(forged.seed, &forged.groups);
+        assert_eq!(verifier.verify(&forged), KeyStatus::Phony(1));
+    }
+
+    #[test]
+    fn test_status() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let key = Key::from_str("007B-BFBF-3049-E324").unwrap();
+        assert_eq!(key.status(&secret), KeyStatus::Valid);
+        let typo = Key::from_str("007B-BFBF-3049-E325").unwrap();
+        assert_eq!(typo.status(&secret), KeyStatus::Invalid);
+        assert!(!typo.checksum_is_valid());
+        assert!(Key::parse_strict("007B-BFBF-3049-E324").is_ok());
+        match Key::parse_strict("007B-BFBF-3049-E325") {
+            Err(Error::InvalidChecksum) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let mut forged = key.clone();
+        forged.groups[0] = Group { left: 0, right: 0 };
+        forged.checksum = checksum(forged.seed, &forged.groups);
+        assert_eq!(forged.status(&secret), KeyStatus::Phony(0));
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let secret = Secret::generate(4, &mut rng);
+        assert_eq!(secret.0.len(), 4);
+        for group in &secret.0 {
+            assert!(!group.left.is_weak());
+            assert!(!group.right.is_weak());
+        }
+        let key = Key::new(1000, &secret);
+        assert!(key.valid(&secret));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_generate_no_groups() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        Secret::generate(0, &mut rng);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let expires = payload::day(2030, 1, 1);
+        let key = Key::new(Payload::new(123).expires(expires).seed(), &secret);
+        assert_eq!(key.payload().expires, Some(expires));
+        assert!(key.valid(&secret));
+        let verifier = Verifier::new(secret.clone()).with_clock(expires);
+        assert_eq!(verifier.verify(&key), KeyStatus::Valid);
+        let verifier = Verifier::new(secret.clone()).with_clock(expires + 1);
+        assert_eq!(verifier.verify(&key), KeyStatus::Expired(expires));
+        // Moving the expiry date breaks the groups, not only the checksum
+        let mut forged = key.clone();
+        forged.seed = Payload::new(123).expires(expires + 365).seed();
+        forged.checksum = checksum(forged.seed, &forged.groups);
+        assert!(!verifier.verify(&forged).is_valid());
+        let verifier = Verifier::new(secret.clone()).require_expiry();
+        assert_eq!(verifier.verify(&Key::new(123, &secret)), KeyStatus::Invalid);
+    }
+
+    #[test]
+    fn test_features() {
+        const PRO: Features = Features::bit(0);
+        const REPORTS: Features = Features::bit(3);
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let key = Key::new(Payload::new(5).features(PRO).seed(), &secret);
+        assert!(key.valid(&secret));
+        assert!(key.grants(PRO));
+        assert!(!key.grants(PRO | REPORTS));
+        let mut forged = key.clone();
+        forged.seed = Payload::new(5).features(PRO | REPORTS).seed();
+        forged.checksum = checksum(forged.seed, &forged.groups);
+        assert!(!forged.valid(&secret));
+    }
+
+    #[test]
+    fn test_identity() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let john = Identity::new("John Smith");
+        let key = Key::with_identity(123, &john, &secret);
+        assert!(key.valid_for(&secret, &Identity::new("john  smith")));
+        assert!(!key.valid_for(&secret, &Identity::new("Jane Smith")));
+        assert!(!key.valid(&secret));
+        let verifier = Verifier::new(secret.clone()).with_identity(john);
+        assert_eq!(verifier.verify(&key), KeyStatus::Valid);
+        assert!(!verifier.verify(&Key::new(123, &secret)).is_valid());
+
+        // The same groups with the hashed value as a plain seed
+        let mut laundered = key.clone();
+        laundered.seed = derive(key.seed, Some(&john)).value;
+        laundered.checksum = checksum(laundered.seed, &laundered.groups);
+        assert!(!laundered.valid(&secret));
+        assert!(!Verifier::new(secret.clone()).verify(&laundered).is_valid());
+    }
+
+    #[test]
+    fn test_laundering() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let expires = payload::day(2027, 1, 1);
+        let key = Key::new(Payload::new(123).expires(expires).seed(), &secret);
+        // The same groups with the hashed value as a plain seed
+        let mut laundered = key.clone();
+        laundered.seed = derive(key.seed, None).value;
+        laundered.checksum = checksum(laundered.seed, &laundered.groups);
+        assert!(!laundered.valid(&secret));
+        let verifier = Verifier::new(secret.clone()).with_clock(payload::day(2030, 1, 1));
+        assert_eq!(verifier.verify(&key), KeyStatus::Expired(expires));
+        assert!(!verifier.verify(&laundered).is_valid());
+    }
+
+    #[test]
+    fn test_legacy_seeds() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        // Seeds without the payload marker keep their groups, only the
+        // lower 32 bits are used
+        let key = Key::new(0x1_0000_007B, &secret);
+        assert_eq!(key.groups(), Key::new(123, &secret).groups());
+        assert_eq!(key.payload(), Payload::new(123));
+        assert_eq!(Key::from_str(&key.to_string()).unwrap(), key);
+        let extended = Key::new(Payload::new(123).seed(), &secret);
+        assert_ne!(extended.groups(), key.groups());
+        assert_eq!(Key::from_str(&extended.to_string()).unwrap(), extended);
+    }
+
+    #[test]
+    fn test_blacklist() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let verifier = Verifier::new(secret.clone())
+            .with_blacklist(vec![123].into_iter().collect());
+        let key = Key::new(123, &secret);
+        assert_eq!(verifier.verify(&key), KeyStatus::Blacklisted);
+        let key = Key::new(124, &secret);
+        assert_eq!(verifier.verify(&key), KeyStatus::Valid);
+    }
+
+    #[test]
+    fn test_secret_non_ascii() {
+        assert!(Secret::from_str("0A6BBFAA6€").is_err());
+        assert!(PartialSecret::from_str("?-ABB7€49301").is_err());
+    }
+
 }
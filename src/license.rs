@@ -0,0 +1,186 @@
+//! License files.
+//!
+//! A license file is a JSON document which bundles a key with the licensee
+//! and product information:
+//!
+//! ```json
+//! {
+//!   "key": "007B-BFBF-3049-E324",
+//!   "licensee": "John Smith",
+//!   "issued": "2026-10-17",
+//!   "product": "Editor",
+//!   "edition": "Pro",
+//!   "mac": "5B1E...C0"
+//! }
+//! ```
+//!
+//! The MAC covers all fields and is keyed with material derived from
+//! the `Secret`, so any change to the file is detected.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+use serde_json;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use hex;
+use mac;
+use payload::{format_date, parse_date};
+use {Day, Identity, Key, KeyStatus, Secret};
+
+const PURPOSE: &str = "serial-number/license";
+
+#[derive(Debug)]
+pub enum LicenseError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The MAC doesn't match the fields.
+    Tampered,
+    /// The MAC is correct, but the key isn't valid.
+    InvalidKey(KeyStatus),
+}
+
+impl error::Error for LicenseError {}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LicenseError::Io(ref e) => write!(f, "io error: {}", e),
+            LicenseError::Format(ref e) => write!(f, "invalid format: {}", e),
+            LicenseError::Tampered => write!(f, "license is tampered"),
+            LicenseError::InvalidKey(status) => write!(f, "invalid key: {:?}", status),
+        }
+    }
+}
+
+impl From<io::Error> for LicenseError {
+    fn from(e: io::Error) -> Self {
+        LicenseError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LicenseError {
+    fn from(e: serde_json::Error) -> Self {
+        LicenseError::Format(e)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct License {
+    pub key: Key,
+    pub licensee: String,
+    #[serde(serialize_with = "serialize_date", deserialize_with = "deserialize_date")]
+    pub issued: Day,
+    pub product: String,
+    pub edition: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    #[serde(flatten)]
+    license: License,
+    mac: String,
+}
+
+impl License {
+    /// Writes the license with its MAC to a string.
+    pub fn seal(&self, secret: &Secret) -> String {
+        let document = Document {
+            license: self.clone(),
+            mac: hex::to_hex(&mac::compute(&secret.clone().into(), PURPOSE, &self.fields())),
+        };
+        serde_json::to_string_pretty(&document).expect("license is always serializable")
+    }
+
+    /// Reads a license, checks its MAC and its key.
+    ///
+    /// The key must be valid for the secret, either as is or bound
+    /// to the licensee with `Key::with_identity`.
+    pub fn open(text: &str, secret: &Secret) -> Result<Self, LicenseError> {
+        let document: Document = serde_json::from_str(text)?;
+        let license = document.license;
+        let tag = hex::from_hex(&document.mac).ok_or(LicenseError::Tampered)?;
+        if !mac::verify(&secret.clone().into(), PURPOSE, &license.fields(), &tag) {
+            return Err(LicenseError::Tampered);
+        }
+        let key = &license.key;
+        if !key.valid(secret) && !key.valid_for(secret, &Identity::new(&license.licensee)) {
+            return Err(LicenseError::InvalidKey(key.status(secret)));
+        }
+        Ok(license)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, secret: &Secret) -> Result<(), LicenseError> {
+        fs::write(path, self.seal(secret))?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, secret: &Secret) -> Result<Self, LicenseError> {
+        let text = fs::read_to_string(path)?;
+        License::open(&text, secret)
+    }
+
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.key.to_string().into_bytes(),
+            self.licensee.clone().into_bytes(),
+            format_date(self.issued).into_bytes(),
+            self.product.clone().into_bytes(),
+            self.edition.clone().into_bytes(),
+        ]
+    }
+}
+
+fn serialize_date<S: Serializer>(day: &Day, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_date(*day))
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Day, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_date(&s).ok_or_else(|| de::Error::custom(format!("invalid date: {}", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use payload::day;
+    use std::str::FromStr;
+
+    fn license(key: Key) -> License {
+        License {
+            key,
+            licensee: "John Smith".to_string(),
+            issued: day(2026, 10, 17),
+            product: "Editor".to_string(),
+            edition: "Pro".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_license() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let license = license(Key::new(123, &secret));
+        let text = license.seal(&secret);
+        assert!(text.contains("\"issued\": \"2026-10-17\""));
+        assert_eq!(License::open(&text, &secret).unwrap(), license);
+
+        let tampered = text.replace("\"Pro\"", "\"Enterprise\"");
+        match License::open(&tampered, &secret) {
+            Err(LicenseError::Tampered) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let bound = Key::with_identity(124, &Identity::new("john smith"), &secret);
+        let text = self::license(bound).seal(&secret);
+        assert!(License::open(&text, &secret).is_ok());
+
+        let other = Secret::from_str("ABB734930FCD-0A6BBFAA6793").unwrap();
+        let foreign = self::license(Key::new(123, &other)).seal(&secret);
+        match License::open(&foreign, &secret) {
+            Err(LicenseError::InvalidKey(KeyStatus::Phony(0))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
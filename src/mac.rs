@@ -0,0 +1,41 @@
+//! MACs keyed with material derived from the known groups of a secret.
+//!
+//! Files written by a release can be checked with the `PartialSecret`
+//! it ships, the held back groups are never needed.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use PartialSecret;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Derives a MAC key for the given purpose, so different documents
+/// never share a key.
+fn derive_key(secret: &PartialSecret, purpose: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(secret.to_string().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Every field is prefixed with its length, so the boundaries between
+/// fields can't be moved.
+fn prepare<F: AsRef<[u8]>>(secret: &PartialSecret, purpose: &str, fields: &[F]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&derive_key(secret, purpose))
+        .expect("HMAC accepts keys of any length");
+    for field in fields {
+        let field = field.as_ref();
+        mac.update(&(field.len() as u64).to_be_bytes());
+        mac.update(field);
+    }
+    mac
+}
+
+pub fn compute<F: AsRef<[u8]>>(secret: &PartialSecret, purpose: &str, fields: &[F]) -> Vec<u8> {
+    prepare(secret, purpose, fields).finalize().into_bytes().to_vec()
+}
+
+/// Checks the MAC in constant time.
+pub fn verify<F: AsRef<[u8]>>(secret: &PartialSecret, purpose: &str, fields: &[F], tag: &[u8]) -> bool {
+    prepare(secret, purpose, fields).verify_slice(tag).is_ok()
+}
@@ -0,0 +1,226 @@
+//! Offline activation for machines without network access.
+//!
+//! The client makes a `Challenge` from its key and a machine fingerprint
+//! and shows it to the user, who passes it to the vendor. The vendor
+//! checks the key with a `Verifier` holding the full `Secret` and its
+//! blacklist, makes a `Response`, and the client checks the response
+//! with its `Verifier`:
+//!
+//! ```text
+//! challenge: 007B-BFBF-3049-E324-8701-7705-1130
+//! response:  180F-3BF9-6217
+//! ```
+//!
+//! Responses are produced with the blocks of the secret like keys, but
+//! their groups are masked differently, so a response is never a valid key.
+//! The value they are produced from is a public hash of the challenge and
+//! the mask is a single byte per block, so every response still tells as
+//! much about the secret as a leaked key. The owner of a valid key can
+//! collect responses for made-up fingerprints, limit the number of
+//! responses per key like activations.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str;
+use identity::fnv1a;
+use {checksum, mix, Byte, Derived, Domain, Error, Group, Key, KeyStatus, Secret, Seed, Verifier};
+
+/// Separates the hash of a challenge from the hash of its seed.
+const SALT: u64 = 0x6F66_666C_696E_6521;
+
+/// Code which the client sends to the vendor. It carries the key and
+/// a hash of the key and the machine fingerprint.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Challenge {
+    key: Key,
+    machine: [Group<Byte>; 2],
+    checksum: Group<Byte>,
+}
+
+impl Challenge {
+    pub fn new(key: &Key, fingerprint: &str) -> Self {
+        let mut bytes = key.to_string().into_bytes();
+        bytes.push(0);
+        bytes.extend_from_slice(fingerprint.as_bytes());
+        let hash = (mix(fnv1a(&bytes)) as u32).to_be_bytes();
+        let machine = [
+            Group { left: hash[0], right: hash[1] },
+            Group { left: hash[2], right: hash[3] },
+        ];
+        let checksum = checksum(key.seed(), &machine);
+        Challenge {
+            key: key.clone(),
+            machine,
+            checksum,
+        }
+    }
+
+    /// Key of the client, `Response::new` checks it before responding.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    fn derive(&self) -> Derived {
+        let machine = self.machine.iter()
+            .fold(0, |acc, group| acc << 16 | (group.left as u64) << 8 | group.right as u64);
+        Derived {
+            value: (mix(self.key.seed() as u64 ^ mix(machine ^ SALT)) & 0xFFFF_FFFF) as Seed,
+            domain: Domain::Response,
+        }
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}-{}", self.key, self.machine[0], self.machine[1], self.checksum)
+    }
+}
+
+impl str::FromStr for Challenge {
+    type Err = Error;
+
+    /// Rejects codes with a wrong checksum of the key or of the whole
+    /// code, they are typed by hand.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items: Vec<&str> = s.split('-').collect();
+        // A key has at least a seed, a group and a checksum
+        if items.len() < 6 {
+            return Err(Error::NotEnoughItems);
+        }
+        let (key, rest) = items.split_at(items.len() - 3);
+        let challenge = Challenge {
+            key: Key::parse_strict(&key.join("-"))?,
+            machine: [rest[0].parse()?, rest[1].parse()?],
+            checksum: rest[2].parse()?,
+        };
+        if checksum(challenge.key.seed(), &challenge.machine) != challenge.checksum {
+            return Err(Error::InvalidChecksum);
+        }
+        Ok(challenge)
+    }
+}
+
+/// Code which the vendor returns for a challenge.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Response {
+    groups: Vec<Group<Byte>>,
+    checksum: Group<Byte>,
+}
+
+impl Response {
+    /// Checks the key of the challenge with the verifier and responds
+    /// with the full secret. The verifier should have the full secret,
+    /// the blacklist and a clock, otherwise the status of the key is
+    /// returned.
+    pub fn new(challenge: &Challenge, secret: &Secret, verifier: &Verifier) -> Result<Self, KeyStatus> {
+        let status = verifier.verify(&challenge.key);
+        if !status.is_valid() {
+            return Err(status);
+        }
+        let derived = challenge.derive();
+        let groups: Vec<Group<Byte>> = secret.0.iter().map(|g| g.produce(derived)).collect();
+        let checksum = checksum(derived.value, &groups);
+        Ok(Response {
+            groups,
+            checksum,
+        })
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for group in &self.groups {
+            write!(f, "{}-", group)?;
+        }
+        write!(f, "{}", self.checksum)
+    }
+}
+
+impl str::FromStr for Response {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut groups = Vec::new();
+        for fragment in s.split('-') {
+            groups.push(fragment.parse()?);
+        }
+        if groups.len() < 2 {
+            return Err(Error::NotEnoughItems);
+        }
+        let checksum = groups.pop().unwrap();
+        Ok(Response {
+            groups,
+            checksum,
+        })
+    }
+}
+
+impl Verifier {
+    /// Checks a response to the challenge with the known groups
+    /// of the secret.
+    pub fn verify_response(&self, challenge: &Challenge, response: &Response) -> bool {
+        if response.groups.len() != self.secret.0.len() {
+            return false;
+        }
+        let derived = challenge.derive();
+        if checksum(derived.value, &response.groups) != response.checksum {
+            return false;
+        }
+        self.secret.0.iter().zip(&response.groups).all(|(block, group)| {
+            block.as_ref().is_none_or(|block| &block.produce(derived) == group)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_challenge_response() {
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let key = Key::new(123, &secret);
+        let challenge = Challenge::new(&key, "machine-a");
+        let text = challenge.to_string();
+        assert!(text.starts_with("007B-BFBF-3049-E324-"));
+        assert_eq!(Challenge::from_str(&text).unwrap(), challenge);
+        for typo in &[text.replace("007B-", "007C-"), text.replace("-1130", "-1131")] {
+            match Challenge::from_str(typo) {
+                Err(Error::InvalidChecksum) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        let full = Verifier::new(secret.clone());
+        let response = Response::new(&challenge, &secret, &full).unwrap();
+        let response = Response::from_str(&response.to_string()).unwrap();
+        let verifier = Verifier::new(secret.partial(&[1]));
+        assert!(verifier.verify_response(&challenge, &response));
+        let other = Challenge::new(&key, "machine-b");
+        assert!(!verifier.verify_response(&other, &response));
+        let foreign = Secret::from_str("ABB734930FCD-0A6BBFAA6793").unwrap();
+        let foreign_verifier = Verifier::new(foreign.clone());
+        let forged = Challenge::new(&Key::new(123, &foreign), "machine-a");
+        let foreign_response = Response::new(&forged, &foreign, &foreign_verifier).unwrap();
+        assert!(!verifier.verify_response(&forged, &foreign_response));
+
+        // Keys which weren't issued or are blacklisted get no response
+        match Response::new(&forged, &secret, &full) {
+            Err(KeyStatus::Phony(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let blacklisted = Verifier::new(secret.clone()).with_blacklist(vec![123].into_iter().collect());
+        assert_eq!(Response::new(&challenge, &secret, &blacklisted), Err(KeyStatus::Blacklisted));
+
+        // A response with the hashed value as a seed isn't a key
+        let derived = challenge.derive().value;
+        let key = Key {
+            seed: derived,
+            groups: response.groups.clone(),
+            checksum: checksum(derived, &response.groups),
+        };
+        assert!(!key.valid(&secret));
+    }
+}
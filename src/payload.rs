@@ -0,0 +1,256 @@
+//! Data packed into the seed of a key.
+//!
+//! Layout of the seed:
+//!
+//! * bits 0..32 - serial number
+//! * bits 32..48 - expiry date, days since 1970-01-01, `0` means never
+//! * bits 48..63 - features granted by the key
+//! * bit 63 - marker of the layout
+//!
+//! The higher bits are folded into the derivation of the groups
+//! (see `Key::new`), so they are covered by the secret as well as by
+//! the checksum.
+//!
+//! Seeds without the marker are read as a plain serial and produce the
+//! same keys as before payloads, only their lower 32 bits are used.
+//! Negative seeds have the marker set, so they are read with this layout
+//! and get other groups than before.
+
+use alloc::string::String;
+use core::ops::BitOr;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+use Seed;
+
+/// Days since 1970-01-01.
+pub type Day = u16;
+
+/// Entitlements granted by a key: editions and add-on modules.
+///
+/// Products define their own constants:
+///
+/// ```
+/// use serial_number::payload::Features;
+///
+/// const PRO: Features = Features::bit(0);
+/// const ENTERPRISE: Features = Features::bit(1);
+/// const REPORTS: Features = Features::bit(8);
+///
+/// let granted = PRO | REPORTS;
+/// assert!(granted.contains(REPORTS));
+/// assert!(!granted.contains(ENTERPRISE));
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Hash)]
+pub struct Features(u16);
+
+impl Features {
+    /// Number of features which fit into a seed.
+    pub const COUNT: u8 = 15;
+
+    pub const NONE: Features = Features(0);
+
+    /// The feature with the given bit, `bit` must be less than `COUNT`.
+    pub const fn bit(bit: u8) -> Self {
+        assert!(bit < Features::COUNT, "feature bit is out of range");
+        Features(1 << bit)
+    }
+
+    /// Returns `None` if the highest bit is set.
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        if bits >> Features::COUNT == 0 {
+            Some(Features(bits))
+        } else {
+            None
+        }
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Features {
+    type Output = Features;
+
+    fn bitor(self, other: Features) -> Features {
+        Features(self.0 | other.0)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Payload {
+    pub serial: u32,
+    pub expires: Option<Day>,
+    pub features: Features,
+}
+
+impl Payload {
+    pub fn new(serial: u32) -> Self {
+        Payload {
+            serial,
+            ..Payload::default()
+        }
+    }
+
+    pub fn expires(mut self, day: Day) -> Self {
+        self.expires = Some(day);
+        self
+    }
+
+    pub fn features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Reads the payload of a seed, seeds without the marker have only
+    /// a serial.
+    pub fn from_seed(seed: Seed) -> Self {
+        if !is_extended(seed) {
+            return Payload::new(seed as u32);
+        }
+        let expires = (seed >> 32) as u16;
+        let features = (seed >> 48) as u16 & 0x7FFF;
+        Payload {
+            serial: seed as u32,
+            expires: if expires == 0 { None } else { Some(expires) },
+            features: Features(features),
+        }
+    }
+
+    pub fn seed(&self) -> Seed {
+        let expires = self.expires.unwrap_or(0) as Seed;
+        let features = self.features.0 as Seed;
+        EXTENDED | (features << 48) | (expires << 32) | self.serial as Seed
+    }
+}
+
+const EXTENDED: Seed = Seed::MIN;
+
+/// Checks the marker of the payload layout.
+pub fn is_extended(seed: Seed) -> bool {
+    seed & EXTENDED != 0
+}
+
+/// Converts a calendar date to days since 1970-01-01.
+pub fn day(year: i32, month: u32, day: u32) -> Day {
+    // Howard Hinnant's `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = (year - era * 400) as u32;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe as i32 - 719_468) as Day
+}
+
+/// Converts days since 1970-01-01 to a calendar date.
+pub fn date(day: Day) -> (i32, u32, u32) {
+    // Howard Hinnant's `civil_from_days`
+    let z = day as i32 + 719_468;
+    let era = z / 146_097;
+    let doe = (z - era * 146_097) as u32;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe as i32 + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+/// Last year which `Day` reaches, it ends on 2149-06-06.
+const MAX_YEAR: i32 = 2149;
+
+/// Parses a `YYYY-MM-DD` date, dates which `Day` can't hold are rejected.
+pub fn parse_date(s: &str) -> Option<Day> {
+    let mut parts = s.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let d = parts.next()?.parse().ok()?;
+    if !(1970..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let result = day(year, month, d);
+    if date(result) == (year, month, d) {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+/// Formats a date as `YYYY-MM-DD`.
+pub fn format_date(day: Day) -> String {
+    let (year, month, d) = date(day);
+    format!("{:04}-{:02}-{:02}", year, month, d)
+}
+
+/// Source of the current date for expiry checks.
+pub trait Clock {
+    fn today(&self) -> Day;
+}
+
+/// A fixed date, useful for tests.
+impl Clock for Day {
+    fn today(&self) -> Day {
+        *self
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn today(&self) -> Day {
+        (now() / 86_400) as Day
+    }
+}
+
+/// Current time of the system clock in seconds since the epoch.
+#[cfg(feature = "std")]
+pub fn now() -> u64 {
+    seconds(SystemTime::now())
+}
+
+/// Seconds since the epoch, `0` for earlier times.
+#[cfg(feature = "std")]
+pub fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        assert_eq!(day(1970, 1, 1), 0);
+        assert_eq!(day(2000, 3, 1), 11_017);
+        assert_eq!(date(11_017), (2000, 3, 1));
+        assert_eq!(date(day(2024, 2, 29)), (2024, 2, 29));
+        assert_eq!(parse_date("2024-02-29"), Some(day(2024, 2, 29)));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2149-06-06"), Some(Day::MAX));
+        assert_eq!(parse_date("2149-06-07"), None);
+        assert_eq!(parse_date("2000000000-01-01"), None);
+        assert_eq!(format_date(day(2030, 1, 5)), "2030-01-05");
+        let payload = Payload::new(0xABCD).expires(day(2030, 12, 31));
+        assert_eq!(Payload::from_seed(payload.seed()), payload);
+        let features = Features::bit(0) | Features::bit(14);
+        let payload = Payload::new(u32::MAX).features(features);
+        assert!(is_extended(payload.seed()));
+        assert_eq!(Payload::from_seed(payload.seed()), payload);
+        assert_eq!(Features::from_bits(0x8000), None);
+        assert_eq!(Payload::from_seed(123), Payload::new(123));
+    }
+}
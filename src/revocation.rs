@@ -0,0 +1,203 @@
+//! Signed revocation lists.
+//!
+//! A revocation list is a text file with the revoked seeds, signed by
+//! the vendor, so it can be shipped with a patch or downloaded by
+//! the product after a release:
+//!
+//! ```text
+//! serial-number revocation list
+//! serial: 7
+//! issued: 2026-10-17
+//! seed: 007B
+//! seed: 1A2B
+//! signature: 9C1F...0B
+//! ```
+//!
+//! Seeds with the payload marker are written as unsigned numbers. A list
+//! with a higher serial supersedes a list with a lower one, check it with
+//! `RevocationList::supersedes` before replacing a stored list.
+
+use ed25519_dalek::{Signature, Signer, Verifier as _};
+use std::collections::BTreeSet;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use document;
+use hex;
+use payload::{format_date, parse_date};
+use signed::{SigningKey, VerifyingKey};
+use {Blacklist, Day, Error, Seed};
+
+const HEADER: &str = "serial-number revocation list";
+
+const CONTEXT: &[u8] = b"serial-number/revocation";
+
+#[derive(Debug)]
+pub enum RevocationError {
+    Io(io::Error),
+    Format(Error),
+    BadSignature,
+}
+
+impl error::Error for RevocationError {}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RevocationError::Io(ref e) => write!(f, "io error: {}", e),
+            RevocationError::Format(ref e) => write!(f, "invalid format: {}", e),
+            RevocationError::BadSignature => write!(f, "bad signature"),
+        }
+    }
+}
+
+impl From<io::Error> for RevocationError {
+    fn from(e: io::Error) -> Self {
+        RevocationError::Io(e)
+    }
+}
+
+impl From<Error> for RevocationError {
+    fn from(e: Error) -> Self {
+        RevocationError::Format(e)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RevocationList {
+    pub serial: u64,
+    pub issued: Day,
+    pub seeds: BTreeSet<Seed>,
+}
+
+impl RevocationList {
+    pub fn new(serial: u64, issued: Day) -> Self {
+        RevocationList {
+            serial,
+            issued,
+            seeds: BTreeSet::new(),
+        }
+    }
+
+    pub fn revoke(&mut self, seed: Seed) -> bool {
+        self.seeds.insert(seed)
+    }
+
+    /// Checks that this list is newer than the other one and should
+    /// replace it.
+    pub fn supersedes(&self, other: &RevocationList) -> bool {
+        self.serial > other.serial
+    }
+
+    /// Writes the list with its signature.
+    pub fn seal(&self, signing_key: &SigningKey) -> String {
+        let mut text = format!("{}\nserial: {}\nissued: {}\n",
+                               HEADER, self.serial, format_date(self.issued));
+        for seed in &self.seeds {
+            text.push_str(&format!("seed: {:04X}\n", seed));
+        }
+        let signature = signing_key.sign(&self.message()).to_bytes();
+        text.push_str(&format!("signature: {}\n", hex::to_hex(&signature)));
+        text
+    }
+
+    /// Reads a list and checks its signature.
+    pub fn open(text: &str, public: &VerifyingKey) -> Result<Self, RevocationError> {
+        let (fields, signature) = document::parse(text, HEADER, "signature")?;
+        let mut serial = None;
+        let mut issued = None;
+        let mut seeds = BTreeSet::new();
+        for (name, value) in fields {
+            match name {
+                "serial" => serial = Some(value.parse().map_err(|_| Error::InvalidFormat)?),
+                "issued" => issued = Some(parse_date(value).ok_or(Error::InvalidFormat)?),
+                "seed" => {
+                    seeds.insert(u64::from_str_radix(value, 16).map_err(Error::from)? as Seed);
+                }
+                _ => return Err(Error::InvalidFormat.into()),
+            }
+        }
+        let list = RevocationList {
+            serial: serial.ok_or(Error::NotEnoughItems)?,
+            issued: issued.ok_or(Error::NotEnoughItems)?,
+            seeds,
+        };
+        let signature = parse_signature(signature.ok_or(Error::NotEnoughItems)?)?;
+        public.verify(&list.message(), &signature)
+            .map_err(|_| RevocationError::BadSignature)?;
+        Ok(list)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, signing_key: &SigningKey) -> Result<(), RevocationError> {
+        fs::write(path, self.seal(signing_key))?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, public: &VerifyingKey) -> Result<Self, RevocationError> {
+        let text = fs::read_to_string(path)?;
+        RevocationList::open(&text, public)
+    }
+
+    fn message(&self) -> Vec<u8> {
+        let mut message = CONTEXT.to_vec();
+        message.extend_from_slice(&self.serial.to_be_bytes());
+        message.extend_from_slice(&self.issued.to_be_bytes());
+        message.extend_from_slice(&(self.seeds.len() as u64).to_be_bytes());
+        for seed in &self.seeds {
+            message.extend_from_slice(&seed.to_be_bytes());
+        }
+        message
+    }
+}
+
+fn parse_signature(s: &str) -> Result<Signature, Error> {
+    let bytes = hex::from_hex(s).ok_or(Error::InvalidFragment)?;
+    Signature::from_slice(&bytes).map_err(|_| Error::InvalidFragment)
+}
+
+impl Blacklist {
+    /// Adds all seeds of a verified revocation list.
+    pub fn merge(&mut self, list: &RevocationList) {
+        self.extend(list.seeds.iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use payload::day;
+    use std::str::FromStr;
+    use {Key, KeyStatus, Secret, Verifier};
+
+    #[test]
+    fn test_revocation_list() {
+        let signing_key = SigningKey::from_bytes(&[9; 32]);
+        let public = signing_key.verifying_key();
+        let mut list = RevocationList::new(7, day(2026, 10, 17));
+        list.revoke(123);
+        list.revoke(0x1A2B);
+        list.revoke(-1);
+        let text = list.seal(&signing_key);
+        assert!(text.contains("seed: 007B\n"));
+        let restored = RevocationList::open(&text, &public).unwrap();
+        assert_eq!(restored, list);
+        assert!(!restored.supersedes(&list));
+        assert!(RevocationList::new(8, day(2026, 10, 18)).supersedes(&list));
+
+        let tampered = text.replace("seed: 007B\n", "");
+        match RevocationList::open(&tampered, &public) {
+            Err(RevocationError::BadSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let other = SigningKey::from_bytes(&[10; 32]).verifying_key();
+        assert!(RevocationList::open(&text, &other).is_err());
+
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        let mut blacklist = Blacklist::new();
+        blacklist.merge(&restored);
+        let verifier = Verifier::new(secret.clone()).with_blacklist(blacklist);
+        assert_eq!(verifier.verify(&Key::new(123, &secret)), KeyStatus::Blacklisted);
+    }
+}
@@ -0,0 +1,52 @@
+//! Serde support. Everything is serialized as its string form and
+//! validated on deserialization with the `FromStr` implementations.
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use alloc::borrow::Cow;
+use {Block, Byte, Group, Key, PartialSecret, Secret};
+
+macro_rules! string_serde {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s: Cow<str> = Deserialize::deserialize(deserializer)?;
+                    s.parse().map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+string_serde!(Key, Secret, PartialSecret, Group<Byte>, Group<Block>, Block);
+
+#[cfg(feature = "signed")]
+string_serde!(::signed::SignedKey);
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use std::str::FromStr;
+    use {Key, Secret};
+
+    #[test]
+    fn test_serde() {
+        let key = Key::from_str("007B-BFBF-3049-E324").unwrap();
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, "\"007B-BFBF-3049-E324\"");
+        assert_eq!(serde_json::from_str::<Key>(&json).unwrap(), key);
+        assert!(serde_json::from_str::<Key>("\"007B-BF\"").is_err());
+
+        let secret: Secret = serde_json::from_str("\"0A6BBFAA6793-ABB734930FCD\"").unwrap();
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"0A6BBFAA6793-ABB734930FCD\"");
+        assert!(serde_json::from_str::<Secret>("\"0A6BBFAA67\"").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Keys signed with Ed25519.
+//!
+//! The payload of a key (serial, expiry and features, see `Payload`)
+//! is signed with the private key of the vendor. Products embed only
+//! the public key, so a verifier can't be turned into a keygen.
+//!
+//! The string form is the seed followed by the signature in groups
+//! of 4 hex digits, like the `Display` of `Key`.
+
+use ed25519_dalek::{Signature, Signer, Verifier as _};
+use std::fmt;
+use std::str;
+use {expiry_status, Blacklist, Byte, Clock, Error, Features, Group, KeyStatus, Payload, Seed};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// Every signed message starts with a context naming its kind, so
+/// a signature of a key is never accepted for a revocation list, a token
+/// or a timestamp signed with the same signing key.
+const CONTEXT: &[u8] = b"serial-number/key";
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SignedKey {
+    seed: Seed,
+    signature: Signature,
+}
+
+impl SignedKey {
+    pub fn new(seed: Seed, signing_key: &SigningKey) -> Self {
+        SignedKey {
+            seed,
+            signature: signing_key.sign(&message(seed)),
+        }
+    }
+
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    pub fn payload(&self) -> Payload {
+        Payload::from_seed(self.seed)
+    }
+
+    /// Features granted by the key. Only trust it after the key is verified.
+    pub fn features(&self) -> Features {
+        self.payload().features
+    }
+
+    pub fn grants(&self, features: Features) -> bool {
+        self.features().contains(features)
+    }
+
+    pub fn valid(&self, public: &VerifyingKey) -> bool {
+        self.status(public).is_valid()
+    }
+
+    /// Checks the signature only.
+    pub fn status(&self, public: &VerifyingKey) -> KeyStatus {
+        match public.verify(&message(self.seed), &self.signature) {
+            Ok(()) => KeyStatus::Valid,
+            Err(_) => KeyStatus::Invalid,
+        }
+    }
+}
+
+fn message(seed: Seed) -> Vec<u8> {
+    let mut message = CONTEXT.to_vec();
+    message.extend_from_slice(&seed.to_be_bytes());
+    message
+}
+
+impl fmt::Display for SignedKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X}", self.seed)?;
+        for pair in self.signature.to_bytes().chunks(2) {
+            write!(f, "-{:02X}{:02X}", pair[0], pair[1])?;
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for SignedKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items: Vec<&str> = s.split('-').collect();
+        if items.len() < 1 + Signature::BYTE_SIZE / 2 {
+            return Err(Error::NotEnoughItems);
+        }
+        if items.len() > 1 + Signature::BYTE_SIZE / 2 {
+            return Err(Error::InvalidFormat);
+        }
+        let seed = u64::from_str_radix(items[0], 16)? as Seed;
+        let mut bytes = [0; Signature::BYTE_SIZE];
+        for (pair, fragment) in bytes.chunks_mut(2).zip(&items[1..]) {
+            let group: Group<Byte> = fragment.parse()?;
+            pair[0] = group.left;
+            pair[1] = group.right;
+        }
+        Ok(SignedKey {
+            seed,
+            signature: Signature::from_bytes(&bytes),
+        })
+    }
+}
+
+/// Counterpart of `Verifier` for signed keys.
+pub struct SignedVerifier {
+    public: VerifyingKey,
+    blacklist: Blacklist,
+    clock: Option<Box<dyn Clock>>,
+    require_expiry: bool,
+}
+
+impl SignedVerifier {
+    pub fn new(public: VerifyingKey) -> Self {
+        SignedVerifier {
+            public,
+            blacklist: Blacklist::new(),
+            clock: None,
+            require_expiry: false,
+        }
+    }
+
+    /// Rejects keys with blacklisted seeds.
+    pub fn with_blacklist(mut self, blacklist: Blacklist) -> Self {
+        self.blacklist = blacklist;
+        self
+    }
+
+    /// Rejects keys which expired before the current date of the clock.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Rejects keys without an expiry date as `Invalid`.
+    pub fn require_expiry(mut self) -> Self {
+        self.require_expiry = true;
+        self
+    }
+
+    pub fn verify(&self, key: &SignedKey) -> KeyStatus {
+        let status = key.status(&self.public);
+        if status != KeyStatus::Valid {
+            return status;
+        }
+        if self.blacklist.contains(key.seed) {
+            return KeyStatus::Blacklisted;
+        }
+        expiry_status(key.payload(), self.clock.as_deref(), self.require_expiry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use payload::day;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_signed_key() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let public = signing_key.verifying_key();
+        let expires = day(2030, 1, 1);
+        let payload = Payload::new(42).expires(expires).features(Features::bit(2));
+        let key = SignedKey::new(payload.seed(), &signing_key);
+        let restored = SignedKey::from_str(&key.to_string()).unwrap();
+        assert_eq!(restored, key);
+        assert!(restored.valid(&public));
+        assert!(restored.grants(Features::bit(2)));
+        let garbage = format!("007B{}", "-1€".repeat(Signature::BYTE_SIZE / 2));
+        assert!(SignedKey::from_str(&garbage).is_err());
+
+        let forged = SignedKey {
+            seed: payload.features(Features::bit(3)).seed(),
+            signature: key.signature,
+        };
+        assert_eq!(forged.status(&public), KeyStatus::Invalid);
+
+        let verifier = SignedVerifier::new(public).with_clock(expires + 1);
+        assert_eq!(verifier.verify(&key), KeyStatus::Expired(expires));
+        let verifier = SignedVerifier::new(public)
+            .with_blacklist(vec![payload.seed()].into_iter().collect());
+        assert_eq!(verifier.verify(&key), KeyStatus::Blacklisted);
+    }
+}
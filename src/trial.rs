@@ -0,0 +1,295 @@
+//! Trial mode for users without a key.
+//!
+//! The trial starts on the first run and its state is kept in a text
+//! file with a MAC keyed with material derived from the known groups of
+//! the secret, so a release needs only its `PartialSecret`:
+//!
+//! ```text
+//! serial-number trial
+//! started: 2026-10-17
+//! key: 007B-BFBF-3049-E324
+//! mac: 5B1E...C0
+//! ```
+//!
+//! Every run records the current time in an `AnchorStore`, a time behind
+//! the anchor means that the clock was moved back. The `key` line is
+//! written when the user enters a valid key and switches the product to
+//! licensed mode.
+//! The key is checked again by `Trial::status`, so it stops counting once
+//! it's blacklisted or expired.
+//!
+//! Removing the file restarts the trial, so keep it in a place which
+//! survives reinstallation.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use anchor::AnchorStore;
+use document;
+use hex;
+use mac;
+use payload::{format_date, now, parse_date};
+use {Day, Error, Key, KeyStatus, PartialSecret, Verifier};
+
+const HEADER: &str = "serial-number trial";
+
+const PURPOSE: &str = "serial-number/trial";
+
+#[derive(Debug)]
+pub enum TrialError {
+    Io(io::Error),
+    Format(Error),
+    /// The MAC doesn't match the state, or the anchor was reset after
+    /// the trial started.
+    Tampered,
+    /// The clock is behind the anchor, in seconds since the epoch.
+    ClockRollback { latest: u64 },
+    InvalidKey(KeyStatus),
+}
+
+impl error::Error for TrialError {}
+
+impl fmt::Display for TrialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrialError::Io(ref e) => write!(f, "io error: {}", e),
+            TrialError::Format(ref e) => write!(f, "invalid format: {}", e),
+            TrialError::Tampered => write!(f, "trial state is tampered"),
+            TrialError::ClockRollback { latest } => {
+                write!(f, "clock is behind the anchor at {}", latest)
+            }
+            TrialError::InvalidKey(status) => write!(f, "invalid key: {:?}", status),
+        }
+    }
+}
+
+impl From<io::Error> for TrialError {
+    fn from(e: io::Error) -> Self {
+        TrialError::Io(e)
+    }
+}
+
+impl From<Error> for TrialError {
+    fn from(e: Error) -> Self {
+        TrialError::Format(e)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum TrialStatus {
+    Trial { days_left: u16 },
+    Expired,
+    Licensed(Key),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct State {
+    started: Day,
+    key: Option<Key>,
+}
+
+impl State {
+    fn seal(&self, secret: &PartialSecret) -> String {
+        let mut text = format!("{}\nstarted: {}\n", HEADER, format_date(self.started));
+        if let Some(ref key) = self.key {
+            text.push_str(&format!("key: {}\n", key));
+        }
+        let tag = mac::compute(secret, PURPOSE, &self.fields());
+        text.push_str(&format!("mac: {}\n", hex::to_hex(&tag)));
+        text
+    }
+
+    fn open(text: &str, secret: &PartialSecret) -> Result<Self, TrialError> {
+        let (fields, tag) = document::parse(text, HEADER, "mac")?;
+        let mut started = None;
+        let mut key = None;
+        for (name, value) in fields {
+            match name {
+                "started" => started = Some(parse_date(value).ok_or(Error::InvalidFormat)?),
+                "key" => key = Some(value.parse()?),
+                _ => return Err(Error::InvalidFormat.into()),
+            }
+        }
+        let state = State {
+            started: started.ok_or(Error::NotEnoughItems)?,
+            key,
+        };
+        let tag = tag.and_then(hex::from_hex).ok_or(TrialError::Tampered)?;
+        if !mac::verify(secret, PURPOSE, &state.fields(), &tag) {
+            return Err(TrialError::Tampered);
+        }
+        Ok(state)
+    }
+
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            format_date(self.started).into_bytes(),
+            self.key.as_ref().map(Key::to_string).unwrap_or_default().into_bytes(),
+        ]
+    }
+}
+
+pub struct Trial {
+    path: PathBuf,
+    secret: PartialSecret,
+    length: u16,
+    state: State,
+    today: Day,
+}
+
+impl Trial {
+    /// Opens the trial state with the current time, see `open_at`.
+    pub fn open<P: AsRef<Path>, S: Into<PartialSecret>>(path: P, secret: S, length: u16, anchors: &mut AnchorStore) -> Result<Self, TrialError> {
+        Trial::open_at(path, secret, length, anchors, now())
+    }
+
+    /// Opens the trial state and records `now`, in seconds since the epoch,
+    /// in the anchors. Starts a trial of `length` days if there is no state
+    /// file yet.
+    pub fn open_at<P: AsRef<Path>, S: Into<PartialSecret>>(path: P, secret: S, length: u16, anchors: &mut AnchorStore, now: u64) -> Result<Self, TrialError> {
+        let path = path.as_ref().to_path_buf();
+        let secret = secret.into();
+        let today = (now / 86_400) as Day;
+        let state = match fs::read_to_string(&path) {
+            Ok(text) => {
+                let state = State::open(&text, &secret)?;
+                // Every run records its time, so the anchor can't be
+                // behind the start unless it was removed
+                if anchors.latest() < state.started as u64 * 86_400 {
+                    return Err(TrialError::Tampered);
+                }
+                state
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                State {
+                    started: today,
+                    key: None,
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !anchors.is_plausible(now) {
+            return Err(TrialError::ClockRollback { latest: anchors.latest() });
+        }
+        anchors.record(now)?;
+        let trial = Trial {
+            path,
+            secret,
+            length,
+            state,
+            today,
+        };
+        trial.save()?;
+        Ok(trial)
+    }
+
+    pub fn started(&self) -> Day {
+        self.state.started
+    }
+
+    /// Days left including today, `0` once the trial is over.
+    pub fn days_remaining(&self) -> u16 {
+        let end = self.state.started as u32 + self.length as u32;
+        end.saturating_sub(self.today as u32) as u16
+    }
+
+    /// Checks the recorded key with the verifier. A key which isn't valid
+    /// anymore falls back to the trial, an expired key expires the product.
+    pub fn status(&self, verifier: &Verifier) -> TrialStatus {
+        if let Some(ref key) = self.state.key {
+            match verifier.verify(key) {
+                KeyStatus::Valid => return TrialStatus::Licensed(key.clone()),
+                KeyStatus::Expired(_) => return TrialStatus::Expired,
+                _ => {}
+            }
+        }
+        match self.days_remaining() {
+            0 => TrialStatus::Expired,
+            days_left => TrialStatus::Trial { days_left },
+        }
+    }
+
+    /// Checks the key with the verifier and switches to licensed mode.
+    pub fn license(&mut self, key: Key, verifier: &Verifier) -> Result<(), TrialError> {
+        let status = verifier.verify(&key);
+        if !status.is_valid() {
+            return Err(TrialError::InvalidKey(status));
+        }
+        self.state.key = Some(key);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), TrialError> {
+        fs::write(&self.path, self.state.seal(&self.secret))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use {Payload, Secret};
+    use testing::TempPath;
+
+    #[test]
+    fn test_trial() {
+        let path = TempPath::new("trial");
+        let anchor = TempPath::new("trial-anchor");
+        let secret = Secret::from_str("0A6BBFAA6793-ABB734930FCD").unwrap();
+        // The release ships only a part of the secret
+        let partial = secret.partial(&[1]);
+        let verifier = Verifier::new(partial.clone());
+        // The store is opened once, its file has the real modification time
+        let mut anchors = AnchorStore::open(&anchor, &partial).unwrap();
+        let start = 20_000 * 86_400;
+
+        let trial = Trial::open_at(&path, partial.clone(), 30, &mut anchors, start).unwrap();
+        assert_eq!(trial.status(&verifier), TrialStatus::Trial { days_left: 30 });
+        let trial = Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 10 * 86_400).unwrap();
+        assert_eq!(trial.days_remaining(), 20);
+        match Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 86_400) {
+            Err(TrialError::ClockRollback { .. }) => {}
+            other => panic!("unexpected result: {:?}", other.map(|trial| trial.status(&verifier))),
+        }
+
+        let text = fs::read_to_string(&path).unwrap();
+        let tampered = text.replace(&format_date(20_000), &format_date(20_010));
+        fs::write(&path, tampered).unwrap();
+        match Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 11 * 86_400) {
+            Err(TrialError::Tampered) => {}
+            other => panic!("unexpected result: {:?}", other.map(|trial| trial.status(&verifier))),
+        }
+        fs::write(&path, text).unwrap();
+        // A removed anchor is detected too
+        let empty = TempPath::new("trial-empty");
+        let mut reset = AnchorStore::open(&empty, &partial).unwrap();
+        match Trial::open_at(&path, partial.clone(), 30, &mut reset, start + 11 * 86_400) {
+            Err(TrialError::Tampered) => {}
+            other => panic!("unexpected result: {:?}", other.map(|trial| trial.status(&verifier))),
+        }
+
+        let mut trial = Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 40 * 86_400).unwrap();
+        assert_eq!(trial.status(&verifier), TrialStatus::Expired);
+        let forged = Key::from_str("007B-BFBF-3049-E325").unwrap();
+        assert!(trial.license(forged, &verifier).is_err());
+        let key = Key::new(123, &secret);
+        let blacklisted = Verifier::new(partial.clone()).with_blacklist(vec![123].into_iter().collect());
+        match trial.license(key.clone(), &blacklisted) {
+            Err(TrialError::InvalidKey(KeyStatus::Blacklisted)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        trial.license(key.clone(), &verifier).unwrap();
+        let trial = Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 41 * 86_400).unwrap();
+        assert_eq!(trial.status(&verifier), TrialStatus::Licensed(key));
+        assert_eq!(trial.status(&blacklisted), TrialStatus::Expired);
+
+        let mut trial = Trial::open_at(&path, partial.clone(), 30, &mut anchors, start + 42 * 86_400).unwrap();
+        let key = Key::new(Payload::new(124).expires(20_050).seed(), &secret);
+        trial.license(key.clone(), &verifier.with_clock(20_042)).unwrap();
+        let verifier = Verifier::new(partial).with_clock(20_051);
+        assert_eq!(trial.status(&verifier), TrialStatus::Expired);
+    }
+}